@@ -0,0 +1,127 @@
+//! Compares the number of wall colliders spawned for the rooms of the world, and how long it takes
+//! to build them. Merging rows is the approach of the bevy_ecs_ldtk example, which the merged
+//! rectangles never do worse than.
+//!
+//! Run with `cargo bench --bench wall_colliders`
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use bevy_ecs_ldtk::ldtk::{LdtkJson, Type};
+use bevy_ecs_ldtk::prelude::GridCoords;
+use sigil::game::ldtk::{connected_walls, merge_wall_rects, merge_wall_rows, trace_wall_outlines};
+
+const WORLD_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/levels/world.ldtk");
+const WALL_LAYER: &str = "GRID";
+const WALL_VALUE: i32 = 1;
+const ITERATIONS: u32 = 1000;
+
+struct Room {
+    identifier: String,
+    walls: HashSet<GridCoords>,
+    width: i32,
+    height: i32,
+}
+
+fn load_rooms() -> Vec<Room> {
+    let json = std::fs::read_to_string(WORLD_PATH).expect("Failed to read the world");
+    let world: LdtkJson = serde_json::from_str(&json).expect("Failed to parse the world");
+
+    let mut rooms = Vec::new();
+    for level in world.levels {
+        for layer in level.layer_instances.unwrap_or_default() {
+            if layer.layer_instance_type != Type::IntGrid || layer.identifier != WALL_LAYER {
+                continue;
+            }
+            // The csv goes row by row from the top, grid coordinates go up from the bottom
+            let walls = layer
+                .int_grid_csv
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value == WALL_VALUE)
+                .map(|(i, _)| GridCoords {
+                    x: i as i32 % layer.c_wid,
+                    y: layer.c_hei - 1 - i as i32 / layer.c_wid,
+                })
+                .collect();
+            rooms.push(Room {
+                identifier: level.identifier.clone(),
+                walls,
+                width: layer.c_wid,
+                height: layer.c_hei,
+            });
+        }
+    }
+    rooms
+}
+
+/// Colliders like [`sigil::game::ldtk::wall_setup`] spawns them: the walls are split into regions
+/// of connected tiles, which are built separately
+fn build(room: &Room, build_region: impl Fn(&HashSet<GridCoords>) -> usize) -> (usize, usize) {
+    let regions = connected_walls(&room.walls, room.walls.iter().copied());
+    let colliders = regions.iter().map(build_region).sum();
+    (regions.len(), colliders)
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let rooms = load_rooms();
+    let rects = |room: &Room| {
+        build(room, |tiles| {
+            merge_wall_rects(tiles, room.width, room.height).len()
+        })
+    };
+    let outlines = |room: &Room| {
+        build(room, |tiles| {
+            trace_wall_outlines(tiles, room.width, room.height).len()
+        })
+    };
+
+    println!(
+        "{:<12} {:>6} {:>8} {:>9} {:>7} {:>9} {:>12} {:>12}",
+        "room", "tiles", "regions", "row merge", "merged", "outlines", "merge time", "outline time"
+    );
+    let mut totals = [0; 5];
+    for room in rooms.iter() {
+        let (regions, merged) = rects(room);
+        let counts = [
+            room.walls.len(),
+            regions,
+            merge_wall_rows(&room.walls, room.width, room.height).len(),
+            merged,
+            outlines(room).1,
+        ];
+        for (total, count) in totals.iter_mut().zip(counts) {
+            *total += count;
+        }
+
+        let merge_time = time(|| {
+            rects(room);
+        });
+        let outline_time = time(|| {
+            outlines(room);
+        });
+        println!(
+            "{:<12} {:>6} {:>8} {:>9} {:>7} {:>9} {:>12?} {:>12?}",
+            room.identifier,
+            counts[0],
+            counts[1],
+            counts[2],
+            counts[3],
+            counts[4],
+            merge_time,
+            outline_time
+        );
+    }
+    println!(
+        "{:<12} {:>6} {:>8} {:>9} {:>7} {:>9}",
+        "total", totals[0], totals[1], totals[2], totals[3], totals[4]
+    );
+}
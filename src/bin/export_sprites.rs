@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// Exports the sprite sheets in `assets/sprites` for bevy_ecs_ldtk / bevy_ecs_tilemap,
+// which don't understand padding around the sheet.
+//
+// Each sheet is a grid of `TILE_SIZE` tiles with `PADDING` pixels around every tile.
+// The tiles are copied to `assets/sprites/export` without the outer padding,
+// with `EXTRUDE` pixels of their edges repeated around them to prevent bleeding.
+// The LDtk tilesets use a spacing of `2 * EXTRUDE` and no padding to match.
+//
+// Sheets are not packed together into a single atlas: each LDtk tileset refers to its own sheet
+// by path, so every exported sheet stays a grid atlas of its own. `manifest.ron` records the
+// layout of each grid, which is all a sprite needs to index into it.
+//
+// Usage: `cargo run --bin export_sprites [-- --force]`
+
+const SPRITE_DIR: &str = "assets/sprites";
+const EXPORT_DIR: &str = "assets/sprites/export";
+const MANIFEST: &str = "manifest.ron";
+
+const TILE_SIZE: u32 = 8;
+/// Padding around each tile in the source sheets
+const PADDING: u32 = 1;
+/// How many pixels of the tile edges are repeated around each exported tile
+const EXTRUDE: u32 = 1;
+
+/// Layout of an exported sheet, and the hash of the source it was exported from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct SheetEntry {
+    hash: u64,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    spacing: u32,
+    columns: u32,
+    rows: u32,
+}
+
+type Manifest = BTreeMap<String, SheetEntry>;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let force = std::env::args().any(|arg| arg == "--force");
+
+    let export_dir = Path::new(EXPORT_DIR);
+    fs::create_dir_all(export_dir)?;
+    let manifest_path = export_dir.join(MANIFEST);
+    let old_manifest: Manifest = fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|manifest| ron::from_str(&manifest).ok())
+        .unwrap_or_default();
+
+    let mut sources: Vec<PathBuf> = fs::read_dir(SPRITE_DIR)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "png"))
+        .collect();
+    sources.sort();
+
+    let mut manifest = Manifest::new();
+    for source in sources {
+        let name = match source.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let output = export_dir.join(&name);
+        let bytes = fs::read(&source)?;
+        let hash = source_hash(&bytes);
+
+        if let Some(entry) = old_manifest.get(&name) {
+            if !force && entry.hash == hash && output.exists() {
+                println!("{} (unchanged)", output.display());
+                manifest.insert(name, entry.clone());
+                continue;
+            }
+        }
+
+        let sheet = image::load_from_memory(&bytes)?.to_rgba8();
+        let (exported, entry) =
+            export_sheet(&sheet, hash).map_err(|err| format!("{}: {err}", source.display()))?;
+        exported.save(&output)?;
+        println!("{}", output.display());
+        manifest.insert(name, entry);
+    }
+
+    let manifest = ron::ser::to_string_pretty(&manifest, ron::ser::PrettyConfig::default())?;
+    fs::write(manifest_path, manifest)?;
+    Ok(())
+}
+
+/// FNV-1a over the source file and the export settings, so changing either re-exports the sheet
+fn source_hash(bytes: &[u8]) -> u64 {
+    let settings = [TILE_SIZE, PADDING, EXTRUDE].map(u32::to_le_bytes);
+    settings
+        .iter()
+        .flatten()
+        .chain(bytes)
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Removes the padding around the tiles of the sheet and packs them into a new grid,
+/// extruding the edges of each tile into the spacing between them
+fn export_sheet(sheet: &RgbaImage, hash: u64) -> Result<(RgbaImage, SheetEntry), String> {
+    let cell = TILE_SIZE + PADDING * 2;
+    let (width, height) = sheet.dimensions();
+    if width % cell != 0 || height % cell != 0 {
+        return Err(format!(
+            "size {width}x{height} isn't a multiple of the {cell}x{cell} tile cells"
+        ));
+    }
+    let (columns, rows) = (width / cell, height / cell);
+
+    let spacing = EXTRUDE * 2;
+    let stride = TILE_SIZE + spacing;
+    let out_width = columns * stride - spacing;
+    let out_height = rows * stride - spacing;
+    let mut exported = RgbaImage::new(out_width, out_height);
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let source = (column * cell + PADDING, row * cell + PADDING);
+            let target = (column * stride, row * stride);
+            blit_extruded(sheet, source, &mut exported, target);
+        }
+    }
+
+    Ok((
+        exported,
+        SheetEntry {
+            hash,
+            width: out_width,
+            height: out_height,
+            tile_size: TILE_SIZE,
+            spacing,
+            columns,
+            rows,
+        },
+    ))
+}
+
+/// Copies a tile, repeating its edge pixels up to `EXTRUDE` pixels outside of it.
+/// Pixels falling outside of the target image are dropped, so the outermost tiles aren't extruded
+/// past the edges of the sheet.
+fn blit_extruded(source: &RgbaImage, from: (u32, u32), target: &mut RgbaImage, to: (u32, u32)) {
+    let extrude = EXTRUDE as i64;
+    let size = TILE_SIZE as i64;
+    for y in -extrude..size + extrude {
+        for x in -extrude..size + extrude {
+            let (target_x, target_y) = (to.0 as i64 + x, to.1 as i64 + y);
+            if target_x < 0
+                || target_y < 0
+                || target_x >= target.width() as i64
+                || target_y >= target.height() as i64
+            {
+                continue;
+            }
+            let source_x = from.0 + x.clamp(0, size - 1) as u32;
+            let source_y = from.1 + y.clamp(0, size - 1) as u32;
+            let pixel: Rgba<u8> = *source.get_pixel(source_x, source_y);
+            target.put_pixel(target_x as u32, target_y as u32, pixel);
+        }
+    }
+}
@@ -1,41 +1,231 @@
+pub mod ability;
+pub mod animation;
+pub mod aseprite;
 pub mod camera;
+pub mod checkpoint;
 pub mod debug;
 pub mod default_plugin_setup;
+pub mod dialogue;
 pub mod entity_instance;
+pub mod health;
+pub mod hud;
+pub mod interaction;
+pub mod item;
 pub mod kinematic_actor;
 pub mod ldtk;
+pub mod level_streaming;
+pub mod loading;
+pub mod menu;
+pub mod navigation;
+pub mod room;
+pub mod save;
+pub mod simulation;
+pub mod state;
 pub mod tile;
 
-use bevy::prelude::*;
+use bevy::{app::PluginGroupBuilder, prelude::*};
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 pub fn init() {
-    App::new()
-        .add_plugin(default_plugin_setup::DefaultPluginSetup)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        // .add_plugin(debug::DebugPlugin)
-        .add_plugin(LdtkPlugin)
-        .insert_resource(LdtkSettings {
+    App::new().add_plugin(SigilPlugin::default()).run();
+}
+
+/// Settings of [`SigilPlugin`]
+#[derive(Clone, Debug)]
+pub struct SigilConfig {
+    /// LDtk project to play, relative to the assets folder
+    pub world_path: String,
+    /// Level the game starts in, unless a save file says otherwise
+    pub start_level: LevelSelection,
+    /// Window to open, `None` to run headless, see [`default_plugin_setup::HeadlessPluginSetup`]
+    pub window: Option<WindowDescriptor>,
+    /// Show the title screen after loading, see [`state::GameState`]
+    pub show_title: bool,
+    /// Collider shapes for the walls of each IntGrid layer
+    pub wall_colliders: ldtk::WallColliderSettings,
+    pub subsystems: SigilSubsystems,
+}
+
+impl Default for SigilConfig {
+    fn default() -> Self {
+        Self {
+            world_path: "levels/world.ldtk".to_string(),
+            start_level: LevelSelection::Identifier("ROOM_0".to_string()),
+            window: Some(default_plugin_setup::DefaultPluginSetup::default().window),
+            show_title: true,
+            wall_colliders: default(),
+            subsystems: SigilSubsystems::default(),
+        }
+    }
+}
+
+impl SigilConfig {
+    /// Default settings without a window
+    pub fn headless() -> Self {
+        Self {
+            window: None,
+            ..default()
+        }
+    }
+}
+
+/// Optional parts of the game
+#[derive(Clone, Copy, Debug)]
+pub struct SigilSubsystems {
+    /// Spawn the neighbours of the current level, see [`level_streaming`]
+    pub level_streaming: bool,
+    /// Pathfinding for [`navigation::NavAgent`]s
+    pub navigation: bool,
+    /// Save points, and loading the save file on startup
+    pub saving: bool,
+}
+
+impl Default for SigilSubsystems {
+    fn default() -> Self {
+        Self {
+            level_streaming: true,
+            navigation: true,
+            saving: true,
+        }
+    }
+}
+
+/// The whole game, including bevy's own plugins
+#[derive(Default)]
+pub struct SigilPlugin {
+    pub config: SigilConfig,
+}
+
+impl SigilPlugin {
+    pub fn new(config: SigilConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for SigilPlugin {
+    fn build(&self, app: &mut App) {
+        match &self.config.window {
+            Some(window) => app.add_plugin(default_plugin_setup::DefaultPluginSetup {
+                window: window.clone(),
+            }),
+            None => app.add_plugin(default_plugin_setup::HeadlessPluginSetup),
+        };
+        // app.add_plugin(debug::DebugPlugin);
+
+        let subsystems = self.config.subsystems;
+        let mut plugins = GamePlugins
+            .build()
+            .set(ldtk::LdtkPluginSetup {
+                // Level streaming decides which levels are spawned instead
+                level_set_from_selection: !subsystems.level_streaming,
+                render: self.config.window.is_some(),
+            })
+            .set(WorldSetupPlugin {
+                world_path: self.config.world_path.clone(),
+                start_level: self.config.start_level.clone(),
+                load_level_neighbors: !subsystems.level_streaming,
+            });
+        if !subsystems.level_streaming {
+            plugins = plugins.disable::<level_streaming::LevelStreamingPlugin>();
+        }
+        if !subsystems.navigation {
+            plugins = plugins.disable::<navigation::NavigationPlugin>();
+        }
+        if !subsystems.saving {
+            plugins = plugins.disable::<save::SavePlugin>();
+        }
+        app.add_plugins(plugins)
+            .insert_resource(state::GameStateSettings {
+                show_title: self.config.show_title,
+            })
+            .insert_resource(self.config.wall_colliders.clone());
+    }
+}
+
+/// Everything the game needs on top of bevy's own plugins, see
+/// [`default_plugin_setup::DefaultPluginSetup`] and [`default_plugin_setup::HeadlessPluginSetup`]
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(RapierPhysicsPlugin::<NoUserData>::default())
+            .add(state::GameStatePlugin)
+            .add(loading::LoadingPlugin)
+            .add(menu::MenuPlugin)
+            .add(ldtk::LdtkPluginSetup::default())
+            .add(ldtk::LdtkHelperPlugin)
+            .add(level_streaming::LevelStreamingPlugin)
+            .add(room::RoomPlugin)
+            .add(camera::GameCameraPlugin)
+            .add(tile::TilePlugin)
+            .add(animation::AnimationPlugin)
+            .add(aseprite::AsepritePlugin)
+            .add(kinematic_actor::KinematicActorPlugin)
+            .add(entity_instance::EntityInstancePlugin)
+            .add(item::ItemPlugin)
+            .add(ability::AbilityPlugin)
+            .add(health::HealthPlugin)
+            .add(hud::HudPlugin)
+            .add(interaction::InteractionPlugin)
+            .add(dialogue::DialoguePlugin)
+            .add(checkpoint::CheckpointPlugin)
+            .add(save::SavePlugin)
+            .add(navigation::NavigationPlugin)
+            .add(WorldSetupPlugin::default())
+    }
+}
+
+/// Settings of the plugins above, and the LDtk world itself
+pub struct WorldSetupPlugin {
+    pub world_path: String,
+    pub start_level: LevelSelection,
+    /// Let bevy_ecs_ldtk spawn the neighbours of the selected level. Only needed without
+    /// [`level_streaming`], which spawns them itself.
+    pub load_level_neighbors: bool,
+}
+
+impl Default for WorldSetupPlugin {
+    fn default() -> Self {
+        let config = SigilConfig::default();
+        Self {
+            world_path: config.world_path,
+            start_level: config.start_level,
+            load_level_neighbors: !config.subsystems.level_streaming,
+        }
+    }
+}
+
+impl Plugin for WorldSetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LdtkSettings {
             level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
-                load_level_neighbors: true,
+                load_level_neighbors: self.load_level_neighbors,
             },
             set_clear_color: SetClearColor::FromLevelBackground,
             ..default()
         })
-        .add_plugin(ldtk::LdtkHelperPlugin)
-        .add_plugin(camera::GameCameraPlugin)
-        .add_plugin(tile::TilePlugin)
-        .add_plugin(kinematic_actor::KinematicActorPlugin)
-        .add_plugin(entity_instance::EntityInstancePlugin)
-        .insert_resource(LevelSelection::Identifier("ROOM_0".to_string()))
-        .add_startup_system(setup)
-        .run();
+        .insert_resource(self.start_level.clone())
+        .insert_resource(WorldPath(self.world_path.clone()))
+        .add_startup_system(setup);
+    }
 }
 
-fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+/// LDtk project spawned on startup
+#[derive(Resource, Clone, Debug)]
+pub struct WorldPath(pub String);
+
+fn setup(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    world_path: Res<WorldPath>,
+    mut required: ResMut<loading::RequiredAssets>,
+) {
+    let ldtk_handle = assets.load(world_path.0.as_str());
+    required.add(&ldtk_handle);
     commands.spawn(LdtkWorldBundle {
-        ldtk_handle: assets.load("levels/world.ldtk"),
+        ldtk_handle,
         ..default()
     });
 }
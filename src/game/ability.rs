@@ -0,0 +1,120 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    entity_instance::player::Player,
+    item::{ItemCollected, ItemId},
+    kinematic_actor::{KaProperties, Platformer},
+};
+
+pub struct AbilityPlugin;
+
+impl Plugin for AbilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Ability>()
+            .register_type::<Abilities>()
+            .init_resource::<AbilityRegistry>()
+            .add_system(ability_unlock)
+            .add_system(ability_effects.after(ability_unlock));
+    }
+}
+
+/// Values of the `ABILITY` enum in LDtk
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Ability {
+    #[default]
+    ExtraJump,
+    Dash,
+    WallJump,
+    MaxHealth,
+}
+
+impl Ability {
+    pub const ALL: [Ability; 4] = [Self::ExtraJump, Self::Dash, Self::WallJump, Self::MaxHealth];
+
+    pub fn from_ldtk(id: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|ability| ability.ldtk_id() == id)
+    }
+
+    /// Enum value identifier in LDtk
+    pub fn ldtk_id(&self) -> &'static str {
+        match self {
+            Self::ExtraJump => "EXTRA_JUMP",
+            Self::Dash => "DASH",
+            Self::WallJump => "WALL_JUMP",
+            Self::MaxHealth => "MAX_HEALTH",
+        }
+    }
+}
+
+/// Which abilities each item unlocks when collected
+#[derive(Resource)]
+pub struct AbilityRegistry {
+    pub items: HashMap<ItemId, Vec<Ability>>,
+}
+
+impl Default for AbilityRegistry {
+    fn default() -> Self {
+        Self {
+            items: [
+                (ItemId::MaxHpTank, vec![Ability::MaxHealth]),
+                (ItemId::Cross, vec![Ability::ExtraJump, Ability::WallJump]),
+                (ItemId::Sigma, vec![Ability::Dash]),
+            ]
+            .into(),
+        }
+    }
+}
+
+/// Unlocked abilities and how many times each has been unlocked
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct Abilities {
+    pub unlocked: HashMap<Ability, u32>,
+}
+
+impl Abilities {
+    pub fn unlock(&mut self, ability: Ability) {
+        *self.unlocked.entry(ability).or_default() += 1;
+    }
+
+    pub fn count(&self, ability: Ability) -> u32 {
+        self.unlocked.get(&ability).copied().unwrap_or(0)
+    }
+
+    pub fn has(&self, ability: Ability) -> bool {
+        self.count(ability) > 0
+    }
+}
+
+fn ability_unlock(
+    mut collected_events: EventReader<ItemCollected>,
+    registry: Res<AbilityRegistry>,
+    mut player_query: Query<&mut Abilities, With<Player>>,
+) {
+    for ItemCollected(item) in collected_events.iter() {
+        if let Some(unlocks) = registry.items.get(item) {
+            for mut abilities in player_query.iter_mut() {
+                for ability in unlocks.iter() {
+                    abilities.unlock(*ability);
+                }
+            }
+        }
+    }
+}
+
+/// Derives the movement capabilities from the unlocked abilities.
+///
+/// Health is handled elsewhere, since it isn't a movement property.
+fn ability_effects(
+    mut query: Query<(&Abilities, &mut KaProperties, Option<&mut Platformer>), Changed<Abilities>>,
+) {
+    for (abilities, mut props, platformer) in query.iter_mut() {
+        props.air_jumps = abilities.count(Ability::ExtraJump);
+        props.wall_jump = abilities.has(Ability::WallJump);
+        if let Some(mut platformer) = platformer {
+            platformer.can_dash = abilities.has(Ability::Dash);
+        }
+    }
+}
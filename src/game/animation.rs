@@ -0,0 +1,237 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::state::is_playing;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SpriteAnimation>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            sprite_animation
+                .label(AnimationSystem)
+                .with_run_criteria(is_playing),
+        );
+    }
+}
+
+/// Advances the [`SpriteAnimation`]s. Systems choosing the clip to play should run before this.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, SystemLabel)]
+pub struct AnimationSystem;
+
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Start over after the last frame
+    #[default]
+    Loop,
+    /// Stay on the last frame
+    Once,
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnimationDirection {
+    #[default]
+    Forward,
+    Reverse,
+    /// Forward and then back, without repeating the first and last frames
+    PingPong,
+    /// Back and then forward, without repeating the last and first frames
+    PingPongReverse,
+}
+
+/// Range of frames in a texture atlas, both ends inclusive
+#[derive(Reflect, FromReflect, Clone, Debug, Default)]
+pub struct AnimationClip {
+    pub first: usize,
+    pub last: usize,
+    /// Frames per second, used for frames without a duration of their own
+    pub fps: f32,
+    pub mode: AnimationMode,
+    pub direction: AnimationDirection,
+    /// Durations of the frames from `first` to `last` in seconds, empty to use `fps` for all
+    pub frame_durations: Vec<f32>,
+}
+
+impl AnimationClip {
+    pub fn looping(first: usize, last: usize, fps: f32) -> Self {
+        Self {
+            first,
+            last,
+            fps,
+            mode: AnimationMode::Loop,
+            ..default()
+        }
+    }
+
+    pub fn once(first: usize, last: usize, fps: f32) -> Self {
+        Self {
+            first,
+            last,
+            fps,
+            mode: AnimationMode::Once,
+            ..default()
+        }
+    }
+
+    /// Clip showing a single frame
+    pub fn still(frame: usize) -> Self {
+        Self::once(frame, frame, 1.0)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.last.saturating_sub(self.first) + 1
+    }
+
+    /// How many steps it takes to play the clip through once, depending on the direction
+    pub fn step_count(&self) -> usize {
+        match self.direction {
+            AnimationDirection::PingPong | AnimationDirection::PingPongReverse
+                if self.frame_count() > 1 =>
+            {
+                self.frame_count() * 2 - 2
+            }
+            _ => self.frame_count(),
+        }
+    }
+
+    /// Frame of the clip (counting from `first`) shown at the given step
+    pub fn frame_at(&self, step: usize) -> usize {
+        let count = self.frame_count();
+        let step = step.min(self.step_count() - 1);
+        let ping_pong = if step < count {
+            step
+        } else {
+            count * 2 - 2 - step
+        };
+        match self.direction {
+            AnimationDirection::Forward => step,
+            AnimationDirection::Reverse => count - 1 - step,
+            AnimationDirection::PingPong => ping_pong,
+            AnimationDirection::PingPongReverse => count - 1 - ping_pong,
+        }
+    }
+
+    /// How long the given step is shown, in seconds
+    pub fn step_duration(&self, step: usize) -> f32 {
+        match self.frame_durations.get(self.frame_at(step)) {
+            Some(duration) => *duration,
+            None if self.fps > 0.0 => 1.0 / self.fps,
+            None => f32::INFINITY,
+        }
+    }
+}
+
+/// Plays named [`AnimationClip`]s on the [`TextureAtlasSprite`] of the same entity
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct SpriteAnimation {
+    pub clips: HashMap<String, AnimationClip>,
+    current: String,
+    step: usize,
+    timer: f32,
+}
+
+impl SpriteAnimation {
+    pub fn with_clip(mut self, name: &str, clip: AnimationClip) -> Self {
+        self.clips.insert(name.to_string(), clip);
+        self
+    }
+
+    /// Switches to the clip, starting from its first frame.
+    /// Playing the clip that is already playing does nothing.
+    pub fn play(&mut self, name: &str) {
+        if self.current != name {
+            self.current = name.to_string();
+            self.step = 0;
+            self.timer = 0.0;
+        }
+    }
+
+    /// Name of the current clip
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn current_clip(&self) -> Option<&AnimationClip> {
+        self.clips.get(&self.current)
+    }
+
+    /// Has a one-shot clip reached its last frame?
+    pub fn is_finished(&self) -> bool {
+        self.current_clip().map_or(false, |clip| {
+            clip.mode == AnimationMode::Once && self.step + 1 >= clip.step_count()
+        })
+    }
+
+    /// Atlas index of the current frame
+    pub fn atlas_index(&self) -> Option<usize> {
+        self.current_clip()
+            .map(|clip| clip.first + clip.frame_at(self.step))
+    }
+
+    fn advance(&mut self, dt: f32) {
+        let (mode, step_count) = match self.current_clip() {
+            Some(clip) => (clip.mode, clip.step_count()),
+            None => return,
+        };
+
+        self.timer += dt;
+        loop {
+            let duration = match self.current_clip() {
+                Some(clip) => clip.step_duration(self.step),
+                None => return,
+            };
+            // Zero length frames would never let the loop end
+            if self.timer < duration || duration <= 0.0 {
+                break;
+            }
+            if mode == AnimationMode::Once && self.step + 1 >= step_count {
+                self.timer = 0.0;
+                break;
+            }
+            self.timer -= duration;
+            self.step = (self.step + 1) % step_count;
+        }
+    }
+}
+
+fn sprite_animation(
+    mut query: Query<(&mut SpriteAnimation, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (mut animation, mut sprite) in query.iter_mut() {
+        animation.advance(dt);
+        if let Some(index) = animation.atlas_index() {
+            if sprite.index != index {
+                sprite.index = index;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(direction: AnimationDirection) -> Vec<usize> {
+        let clip = AnimationClip {
+            direction,
+            ..AnimationClip::looping(4, 7, 10.0)
+        };
+        (0..clip.step_count())
+            .map(|step| clip.frame_at(step))
+            .collect()
+    }
+
+    #[test]
+    fn directions() {
+        assert_eq!(frames(AnimationDirection::Forward), vec![0, 1, 2, 3]);
+        assert_eq!(frames(AnimationDirection::Reverse), vec![3, 2, 1, 0]);
+        assert_eq!(frames(AnimationDirection::PingPong), vec![0, 1, 2, 3, 2, 1]);
+        assert_eq!(
+            frames(AnimationDirection::PingPongReverse),
+            vec![3, 2, 1, 0, 1, 2]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::{BoxedFuture, HashMap},
+};
+
+use super::animation::{AnimationClip, AnimationDirection, AnimationMode, SpriteAnimation};
+
+mod parse;
+
+pub use parse::*;
+
+pub struct AsepritePlugin;
+
+impl Plugin for AsepritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Aseprite>()
+            .init_asset_loader::<AsepriteLoader>()
+            .add_system(aseprite_clips);
+    }
+}
+
+/// Sprite loaded from an `.aseprite` file.
+///
+/// The frames are laid out left to right in a texture atlas, which can also be loaded directly
+/// with the `atlas` label, e.g. `assets.load("sprites/player.aseprite#atlas")`.
+/// Each tag becomes an [`AnimationClip`] of the same name.
+#[derive(TypeUuid, Debug)]
+#[uuid = "4f6e4a0f-8d2b-4a57-9d8c-2f3b1c6e5a91"]
+pub struct Aseprite {
+    pub image: Handle<Image>,
+    pub atlas: Handle<TextureAtlas>,
+    pub size: Vec2,
+    /// Duration of each frame, in seconds
+    pub frame_durations: Vec<f32>,
+    pub clips: HashMap<String, AnimationClip>,
+    pub slices: HashMap<String, Slice>,
+}
+
+impl Aseprite {
+    /// Animation with every tag as a clip
+    pub fn animation(&self) -> SpriteAnimation {
+        self.clips
+            .iter()
+            .fold(SpriteAnimation::default(), |animation, (name, clip)| {
+                animation.with_clip(name, clip.clone())
+            })
+    }
+}
+
+#[derive(Default)]
+pub struct AsepriteLoader;
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file = AsepriteFile::parse(bytes)?;
+
+            let frame_count = file.frames.len().max(1) as u32;
+            let atlas_width = file.width * frame_count;
+            let mut data = vec![0u8; (atlas_width * file.height * 4) as usize];
+            for (index, frame) in file.frames.iter().enumerate() {
+                for y in 0..file.height as usize {
+                    let row = y * file.width as usize * 4;
+                    let target = (y * atlas_width as usize + index * file.width as usize) * 4;
+                    data[target..target + file.width as usize * 4]
+                        .copy_from_slice(&frame[row..row + file.width as usize * 4]);
+                }
+            }
+
+            let image = Image::new(
+                Extent3d {
+                    width: atlas_width,
+                    height: file.height,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                data,
+                TextureFormat::Rgba8UnormSrgb,
+            );
+            let image = load_context.set_labeled_asset("image", LoadedAsset::new(image));
+
+            let size = Vec2::new(file.width as f32, file.height as f32);
+            let mut atlas =
+                TextureAtlas::new_empty(image.clone(), Vec2::new(atlas_width as f32, size.y));
+            for index in 0..file.frames.len() {
+                let min = Vec2::new(index as f32 * size.x, 0.0);
+                atlas.add_texture(Rect::from_corners(min, min + size));
+            }
+            let atlas = load_context.set_labeled_asset("atlas", LoadedAsset::new(atlas));
+
+            let frame_durations: Vec<f32> = file
+                .durations
+                .iter()
+                .map(|duration| *duration as f32 / 1000.0)
+                .collect();
+
+            let clips = file
+                .tags
+                .iter()
+                .map(|tag| {
+                    let (first, last) = (tag.from.min(tag.to), tag.from.max(tag.to));
+                    let clip = AnimationClip {
+                        first,
+                        last,
+                        fps: 0.0,
+                        mode: if tag.repeat == 0 {
+                            AnimationMode::Loop
+                        } else {
+                            AnimationMode::Once
+                        },
+                        direction: match tag.direction {
+                            TagDirection::Forward => AnimationDirection::Forward,
+                            TagDirection::Reverse => AnimationDirection::Reverse,
+                            TagDirection::PingPong => AnimationDirection::PingPong,
+                            TagDirection::PingPongReverse => AnimationDirection::PingPongReverse,
+                        },
+                        frame_durations: frame_durations
+                            .get(first..=last)
+                            .map(|durations| durations.to_vec())
+                            .unwrap_or_default(),
+                    };
+                    (tag.name.clone(), clip)
+                })
+                .collect();
+
+            let slices = file
+                .slices
+                .into_iter()
+                .map(|slice| (slice.name.clone(), slice))
+                .collect();
+
+            load_context.set_default_asset(LoadedAsset::new(Aseprite {
+                image,
+                atlas,
+                size,
+                frame_durations,
+                clips,
+                slices,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite", "ase"]
+    }
+}
+
+/// Adds the tags of the [`Aseprite`] to the [`SpriteAnimation`] of the same entity,
+/// replacing the clips of the same name. Reapplied when the file is hot-reloaded.
+fn aseprite_clips(
+    mut aseprite_events: EventReader<AssetEvent<Aseprite>>,
+    aseprites: Res<Assets<Aseprite>>,
+    mut query: Query<(Entity, &Handle<Aseprite>, &mut SpriteAnimation)>,
+    added_query: Query<(), Added<Handle<Aseprite>>>,
+) {
+    let changed: Vec<Handle<Aseprite>> = aseprite_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                Some(handle.clone_weak())
+            }
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+
+    for (entity, handle, mut animation) in query.iter_mut() {
+        if !added_query.contains(entity) && !changed.contains(handle) {
+            continue;
+        }
+        if let Some(aseprite) = aseprites.get(handle) {
+            for (name, clip) in aseprite.clips.iter() {
+                animation.clips.insert(name.clone(), clip.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,940 @@
+use bevy::log::warn;
+use flate2::read::ZlibDecoder;
+use std::{fmt, io::Read};
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_SLICE: u16 = 0x2022;
+
+const LAYER_BLEND_NORMAL: u16 = 0;
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_FLAG_REFERENCE: u16 = 64;
+const HEADER_FLAG_LAYER_OPACITY: u32 = 1;
+
+#[derive(Debug)]
+pub enum AsepriteError {
+    UnexpectedEnd,
+    InvalidMagic(u16),
+    UnsupportedColorDepth(u16),
+    Decompress(std::io::Error),
+}
+
+impl fmt::Display for AsepriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of file"),
+            Self::InvalidMagic(magic) => write!(f, "invalid magic number {magic:#06x}"),
+            Self::UnsupportedColorDepth(depth) => write!(f, "unsupported color depth {depth}"),
+            Self::Decompress(err) => write!(f, "failed to decompress cel: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AsepriteError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+#[derive(Clone, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: TagDirection,
+    /// How many times the tag is played, 0 being forever
+    pub repeat: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SliceRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Slice {
+    pub name: String,
+    /// Bounds on the frame the first key applies to, in pixels from the top left corner
+    pub bounds: SliceRect,
+    /// Center part of a 9-patch slice, relative to the bounds
+    pub center: Option<SliceRect>,
+    /// Pivot point relative to the bounds
+    pub pivot: Option<(i32, i32)>,
+}
+
+/// A decoded Aseprite file with every frame flattened into RGBA pixels.
+///
+/// Format: https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md
+///
+/// Only what's needed for sprites is read: visible layers, cels, tags and slices.
+/// Layers are composited with normal blending, other blend modes are drawn as normal with a
+/// warning.
+#[derive(Clone, Debug)]
+pub struct AsepriteFile {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA pixels of each frame, row by row from the top left corner
+    pub frames: Vec<Vec<u8>>,
+    /// Duration of each frame in milliseconds
+    pub durations: Vec<u16>,
+    pub tags: Vec<Tag>,
+    pub slices: Vec<Slice>,
+}
+
+struct Layer {
+    visible: bool,
+    opacity: u8,
+}
+
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    opacity: u8,
+    content: CelContent,
+}
+
+enum CelContent {
+    Image {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    Linked(usize),
+}
+
+/// Little endian cursor over the file bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AsepriteError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(AsepriteError::UnexpectedEnd)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), AsepriteError> {
+        self.take(len).map(|_| ())
+    }
+
+    fn byte(&mut self) -> Result<u8, AsepriteError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, AsepriteError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn short(&mut self) -> Result<i16, AsepriteError> {
+        Ok(self.word()? as i16)
+    }
+
+    fn dword(&mut self) -> Result<u32, AsepriteError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn long(&mut self) -> Result<i32, AsepriteError> {
+        Ok(self.dword()? as i32)
+    }
+
+    fn string(&mut self) -> Result<String, AsepriteError> {
+        let len = self.word()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let slice = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        slice
+    }
+}
+
+impl AsepriteFile {
+    pub fn parse(bytes: &[u8]) -> Result<Self, AsepriteError> {
+        let mut header = Reader::new(bytes);
+        header.skip(4)?;
+        let magic = header.word()?;
+        if magic != HEADER_MAGIC {
+            return Err(AsepriteError::InvalidMagic(magic));
+        }
+        let frame_count = header.word()? as usize;
+        let width = header.word()? as u32;
+        let height = header.word()? as u32;
+        let color_depth = header.word()?;
+        if !matches!(color_depth, 8 | 16 | 32) {
+            return Err(AsepriteError::UnsupportedColorDepth(color_depth));
+        }
+        let flags = header.dword()?;
+        header.skip(2 + 4 + 4)?;
+        let transparent_index = header.byte()?;
+
+        let mut reader = Reader::new(bytes);
+        reader.skip(128)?;
+
+        let mut palette = vec![[0u8; 4]; 256];
+        let mut layers: Vec<Layer> = Vec::new();
+        // Group layers and their depth, so children of hidden groups can be hidden too
+        let mut hidden_depth: Option<u16> = None;
+        let mut frame_cels: Vec<Vec<Cel>> = Vec::with_capacity(frame_count);
+        let mut durations = Vec::with_capacity(frame_count);
+        let mut tags = Vec::new();
+        let mut slices = Vec::new();
+
+        for _ in 0..frame_count {
+            let frame_start = reader.pos;
+            let frame_size = reader.dword()? as usize;
+            let frame_magic = reader.word()?;
+            if frame_magic != FRAME_MAGIC {
+                return Err(AsepriteError::InvalidMagic(frame_magic));
+            }
+            let old_chunk_count = reader.word()? as usize;
+            durations.push(reader.word()?);
+            reader.skip(2)?;
+            let chunk_count = match reader.dword()? as usize {
+                0 => old_chunk_count,
+                count => count,
+            };
+
+            let mut cels = Vec::new();
+            for _ in 0..chunk_count {
+                let chunk_size = reader.dword()? as usize;
+                let chunk_type = reader.word()?;
+                let mut chunk = Reader::new(reader.take(chunk_size.saturating_sub(6))?);
+
+                match chunk_type {
+                    CHUNK_LAYER => {
+                        let layer_flags = chunk.word()?;
+                        let layer_type = chunk.word()?;
+                        let depth = chunk.word()?;
+                        chunk.skip(2 + 2)?;
+                        let blend_mode = chunk.word()?;
+                        let opacity = chunk.byte()?;
+                        chunk.skip(3)?;
+                        let name = chunk.string()?;
+                        let opacity_valid = flags & HEADER_FLAG_LAYER_OPACITY != 0;
+                        if blend_mode != LAYER_BLEND_NORMAL {
+                            warn!("Layer {name} has blend mode {blend_mode}, drawn as normal");
+                        }
+                        if !opacity_valid && opacity != 255 {
+                            warn!("Ignoring opacity {opacity} of layer {name}, not marked valid");
+                        }
+
+                        if hidden_depth.map_or(false, |hidden| depth <= hidden) {
+                            hidden_depth = None;
+                        }
+                        let visible = hidden_depth.is_none()
+                            && layer_flags & LAYER_FLAG_VISIBLE != 0
+                            && layer_flags & LAYER_FLAG_REFERENCE == 0;
+                        // Hidden groups hide everything inside them
+                        if !visible && layer_type == 1 && hidden_depth.is_none() {
+                            hidden_depth = Some(depth);
+                        }
+
+                        layers.push(Layer {
+                            visible,
+                            opacity: if opacity_valid { opacity } else { 255 },
+                        });
+                    }
+                    CHUNK_CEL => {
+                        let layer = chunk.word()? as usize;
+                        let x = chunk.short()? as i32;
+                        let y = chunk.short()? as i32;
+                        let opacity = chunk.byte()?;
+                        let cel_type = chunk.word()?;
+                        chunk.skip(2 + 5)?;
+                        let content = match cel_type {
+                            0 => {
+                                let width = chunk.word()? as u32;
+                                let height = chunk.word()? as u32;
+                                CelContent::Image {
+                                    width,
+                                    height,
+                                    pixels: chunk.rest().to_vec(),
+                                }
+                            }
+                            1 => CelContent::Linked(chunk.word()? as usize),
+                            2 => {
+                                let width = chunk.word()? as u32;
+                                let height = chunk.word()? as u32;
+                                let mut pixels = Vec::new();
+                                ZlibDecoder::new(chunk.rest())
+                                    .read_to_end(&mut pixels)
+                                    .map_err(AsepriteError::Decompress)?;
+                                CelContent::Image {
+                                    width,
+                                    height,
+                                    pixels,
+                                }
+                            }
+                            // Tilemaps are not supported
+                            _ => continue,
+                        };
+                        cels.push(Cel {
+                            layer,
+                            x,
+                            y,
+                            opacity,
+                            content,
+                        });
+                    }
+                    CHUNK_TAGS => {
+                        let count = chunk.word()?;
+                        chunk.skip(8)?;
+                        for _ in 0..count {
+                            let from = chunk.word()? as usize;
+                            let to = chunk.word()? as usize;
+                            let direction = match chunk.byte()? {
+                                1 => TagDirection::Reverse,
+                                2 => TagDirection::PingPong,
+                                3 => TagDirection::PingPongReverse,
+                                _ => TagDirection::Forward,
+                            };
+                            let repeat = chunk.word()?;
+                            chunk.skip(6 + 3 + 1)?;
+                            tags.push(Tag {
+                                name: chunk.string()?,
+                                from,
+                                to,
+                                direction,
+                                repeat,
+                            });
+                        }
+                    }
+                    CHUNK_PALETTE => {
+                        chunk.skip(4)?;
+                        let first = chunk.dword()? as usize;
+                        let last = chunk.dword()? as usize;
+                        chunk.skip(8)?;
+                        for index in first..=last {
+                            let entry_flags = chunk.word()?;
+                            let color =
+                                [chunk.byte()?, chunk.byte()?, chunk.byte()?, chunk.byte()?];
+                            if entry_flags & 1 != 0 {
+                                chunk.string()?;
+                            }
+                            if let Some(entry) = palette.get_mut(index) {
+                                *entry = color;
+                            }
+                        }
+                    }
+                    CHUNK_OLD_PALETTE => {
+                        let packets = chunk.word()?;
+                        let mut index = 0usize;
+                        for _ in 0..packets {
+                            index += chunk.byte()? as usize;
+                            let count = match chunk.byte()? {
+                                0 => 256,
+                                count => count as usize,
+                            };
+                            for _ in 0..count {
+                                let color = [chunk.byte()?, chunk.byte()?, chunk.byte()?, 255];
+                                if let Some(entry) = palette.get_mut(index) {
+                                    *entry = color;
+                                }
+                                index += 1;
+                            }
+                        }
+                    }
+                    CHUNK_SLICE => {
+                        let key_count = chunk.dword()?;
+                        let slice_flags = chunk.dword()?;
+                        chunk.skip(4)?;
+                        let name = chunk.string()?;
+                        // Only the first key is used, slices rarely change between frames
+                        if key_count > 0 {
+                            chunk.skip(4)?;
+                            let bounds = SliceRect {
+                                x: chunk.long()?,
+                                y: chunk.long()?,
+                                width: chunk.dword()?,
+                                height: chunk.dword()?,
+                            };
+                            let center = if slice_flags & 1 != 0 {
+                                Some(SliceRect {
+                                    x: chunk.long()?,
+                                    y: chunk.long()?,
+                                    width: chunk.dword()?,
+                                    height: chunk.dword()?,
+                                })
+                            } else {
+                                None
+                            };
+                            let pivot = if slice_flags & 2 != 0 {
+                                Some((chunk.long()?, chunk.long()?))
+                            } else {
+                                None
+                            };
+                            slices.push(Slice {
+                                name,
+                                bounds,
+                                center,
+                                pivot,
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            reader.pos = frame_start + frame_size;
+            frame_cels.push(cels);
+        }
+
+        if color_depth == 8 {
+            palette[transparent_index as usize] = [0, 0, 0, 0];
+        }
+
+        let mut frames = Vec::with_capacity(frame_count);
+        for frame in 0..frame_cels.len() {
+            let mut pixels = vec![0u8; (width * height * 4) as usize];
+            let mut cels: Vec<&Cel> = frame_cels[frame].iter().collect();
+            cels.sort_by_key(|cel| cel.layer);
+
+            for cel in cels {
+                let layer = match layers.get(cel.layer) {
+                    Some(layer) if layer.visible => layer,
+                    _ => continue,
+                };
+                // Linked cels use the image of the same layer in another frame
+                let image = match &cel.content {
+                    CelContent::Image { .. } => Some(&cel.content),
+                    CelContent::Linked(linked_frame) => frame_cels
+                        .get(*linked_frame)
+                        .and_then(|cels| cels.iter().find(|other| other.layer == cel.layer))
+                        .map(|other| &other.content),
+                };
+                if let Some(CelContent::Image {
+                    width: cel_width,
+                    height: cel_height,
+                    pixels: cel_pixels,
+                }) = image
+                {
+                    let opacity = cel.opacity as u32 * layer.opacity as u32 / 255;
+                    blit(
+                        &mut pixels,
+                        (width, height),
+                        CelPixels {
+                            pixels: cel_pixels,
+                            size: (*cel_width, *cel_height),
+                            offset: (cel.x, cel.y),
+                            opacity: opacity as u8,
+                        },
+                        color_depth,
+                        &palette,
+                    );
+                }
+            }
+            frames.push(pixels);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            frames,
+            durations,
+            tags,
+            slices,
+        })
+    }
+}
+
+/// Image of a cel, positioned on the frame
+struct CelPixels<'a> {
+    pixels: &'a [u8],
+    size: (u32, u32),
+    offset: (i32, i32),
+    opacity: u8,
+}
+
+/// Composites cel pixels on top of the frame with normal blending
+fn blit(
+    target: &mut [u8],
+    (width, height): (u32, u32),
+    cel: CelPixels,
+    color_depth: u16,
+    palette: &[[u8; 4]],
+) {
+    let CelPixels {
+        pixels: source,
+        size: (source_width, source_height),
+        offset: (offset_x, offset_y),
+        opacity,
+    } = cel;
+    let bytes_per_pixel = color_depth as usize / 8;
+    for sy in 0..source_height as i32 {
+        let ty = sy + offset_y;
+        if ty < 0 || ty >= height as i32 {
+            continue;
+        }
+        for sx in 0..source_width as i32 {
+            let tx = sx + offset_x;
+            if tx < 0 || tx >= width as i32 {
+                continue;
+            }
+            let si = (sy as usize * source_width as usize + sx as usize) * bytes_per_pixel;
+            let src = match (color_depth, source.get(si..si + bytes_per_pixel)) {
+                (32, Some(p)) => [p[0], p[1], p[2], p[3]],
+                (16, Some(p)) => [p[0], p[0], p[0], p[1]],
+                (8, Some(p)) => palette[p[0] as usize],
+                _ => continue,
+            };
+
+            let ti = (ty as usize * width as usize + tx as usize) * 4;
+            let dst = &mut target[ti..ti + 4];
+            let src_alpha = src[3] as f32 / 255.0 * opacity as f32 / 255.0;
+            let dst_alpha = dst[3] as f32 / 255.0;
+            let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+            if out_alpha <= 0.0 {
+                continue;
+            }
+            for c in 0..3 {
+                let blended = (src[c] as f32 * src_alpha
+                    + dst[c] as f32 * dst_alpha * (1.0 - src_alpha))
+                    / out_alpha;
+                dst[c] = blended.round() as u8;
+            }
+            dst[3] = (out_alpha * 255.0).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn word(bytes: &mut Vec<u8>, value: u16) {
+        bytes.extend(value.to_le_bytes());
+    }
+
+    fn dword(bytes: &mut Vec<u8>, value: u32) {
+        bytes.extend(value.to_le_bytes());
+    }
+
+    fn string(bytes: &mut Vec<u8>, value: &str) {
+        word(bytes, value.len() as u16);
+        bytes.extend(value.as_bytes());
+    }
+
+    fn chunk(kind: u16, data: Vec<u8>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        dword(&mut bytes, data.len() as u32 + 6);
+        word(&mut bytes, kind);
+        bytes.extend(data);
+        bytes
+    }
+
+    fn layer(
+        name: &str,
+        flags: u16,
+        layer_type: u16,
+        depth: u16,
+        blend_mode: u16,
+        opacity: u8,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        word(&mut data, flags);
+        word(&mut data, layer_type);
+        word(&mut data, depth);
+        data.extend([0; 4]);
+        word(&mut data, blend_mode);
+        data.push(opacity);
+        data.extend([0; 3]);
+        string(&mut data, name);
+        chunk(CHUNK_LAYER, data)
+    }
+
+    fn visible_layer(name: &str) -> Vec<u8> {
+        layer(name, LAYER_FLAG_VISIBLE, 0, 0, LAYER_BLEND_NORMAL, 255)
+    }
+
+    fn cel_header(layer: u16, (x, y): (i16, i16), opacity: u8, cel_type: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        word(&mut data, layer);
+        word(&mut data, x as u16);
+        word(&mut data, y as u16);
+        data.push(opacity);
+        word(&mut data, cel_type);
+        data.extend([0; 2 + 5]);
+        data
+    }
+
+    /// Cel of `width` columns of RGBA `pixels`
+    fn raw_cel(layer: u16, offset: (i16, i16), width: u16, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut data = cel_header(layer, offset, 255, 0);
+        word(&mut data, width);
+        word(&mut data, (pixels.len() / width as usize) as u16);
+        data.extend(pixels.concat());
+        chunk(CHUNK_CEL, data)
+    }
+
+    fn zlib_cel(layer: u16, offset: (i16, i16), width: u16, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut data = cel_header(layer, offset, 255, 2);
+        word(&mut data, width);
+        word(&mut data, (pixels.len() / width as usize) as u16);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pixels.concat()).unwrap();
+        data.extend(encoder.finish().unwrap());
+        chunk(CHUNK_CEL, data)
+    }
+
+    fn linked_cel(layer: u16, frame: u16) -> Vec<u8> {
+        let mut data = cel_header(layer, (0, 0), 255, 1);
+        word(&mut data, frame);
+        chunk(CHUNK_CEL, data)
+    }
+
+    fn tags(tags: &[(&str, u16, u16, u8, u16)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        word(&mut data, tags.len() as u16);
+        data.extend([0; 8]);
+        for (name, from, to, direction, repeat) in tags {
+            word(&mut data, *from);
+            word(&mut data, *to);
+            data.push(*direction);
+            word(&mut data, *repeat);
+            data.extend([0; 6 + 3 + 1]);
+            string(&mut data, name);
+        }
+        chunk(CHUNK_TAGS, data)
+    }
+
+    /// 32 bit file with the given header flags, and the duration and chunks of each frame
+    fn file(size: (u16, u16), flags: u32, frames: &[(u16, Vec<Vec<u8>>)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        dword(&mut bytes, 0);
+        word(&mut bytes, HEADER_MAGIC);
+        word(&mut bytes, frames.len() as u16);
+        word(&mut bytes, size.0);
+        word(&mut bytes, size.1);
+        word(&mut bytes, 32);
+        dword(&mut bytes, flags);
+        bytes.resize(128, 0);
+
+        for (duration, chunks) in frames {
+            let data = chunks.concat();
+            dword(&mut bytes, data.len() as u32 + 16);
+            word(&mut bytes, FRAME_MAGIC);
+            word(&mut bytes, 0);
+            word(&mut bytes, *duration);
+            word(&mut bytes, 0);
+            dword(&mut bytes, chunks.len() as u32);
+            bytes.extend(data);
+        }
+        let len = bytes.len() as u32;
+        bytes[..4].copy_from_slice(&len.to_le_bytes());
+        bytes
+    }
+
+    fn pixel(file: &AsepriteFile, frame: usize, (x, y): (usize, usize)) -> [u8; 4] {
+        let i = (y * file.width as usize + x) * 4;
+        file.frames[frame][i..i + 4].try_into().unwrap()
+    }
+
+    fn two_frames() -> Vec<u8> {
+        file(
+            (2, 2),
+            HEADER_FLAG_LAYER_OPACITY,
+            &[
+                (
+                    100,
+                    vec![visible_layer("base"), raw_cel(0, (0, 0), 2, &[RED, BLUE])],
+                ),
+                (150, vec![zlib_cel(0, (1, 1), 1, &[BLUE])]),
+            ],
+        )
+    }
+
+    #[test]
+    fn header() {
+        let file = AsepriteFile::parse(&two_frames()).unwrap();
+        assert_eq!((file.width, file.height), (2, 2));
+        assert_eq!(file.frames.len(), 2);
+        assert_eq!(file.durations, vec![100, 150]);
+        assert!(file.frames.iter().all(|frame| frame.len() == 2 * 2 * 4));
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let mut bytes = two_frames();
+        bytes[4] = 0;
+        assert!(matches!(
+            AsepriteFile::parse(&bytes),
+            Err(AsepriteError::InvalidMagic(_))
+        ));
+    }
+
+    #[test]
+    fn raw_cel_pixels() {
+        let file = AsepriteFile::parse(&two_frames()).unwrap();
+        assert_eq!(pixel(&file, 0, (0, 0)), RED);
+        assert_eq!(pixel(&file, 0, (1, 0)), BLUE);
+        assert_eq!(pixel(&file, 0, (0, 1)), CLEAR);
+        assert_eq!(pixel(&file, 0, (1, 1)), CLEAR);
+    }
+
+    #[test]
+    fn zlib_cel_pixels() {
+        let file = AsepriteFile::parse(&two_frames()).unwrap();
+        assert_eq!(pixel(&file, 1, (0, 0)), CLEAR);
+        assert_eq!(pixel(&file, 1, (1, 1)), BLUE);
+    }
+
+    #[test]
+    fn cel_outside_of_frame_is_clipped() {
+        let bytes = file(
+            (2, 1),
+            0,
+            &[(
+                100,
+                vec![
+                    visible_layer("base"),
+                    raw_cel(0, (-1, 0), 3, &[RED, BLUE, RED]),
+                ],
+            )],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        assert_eq!(pixel(&file, 0, (0, 0)), BLUE);
+        assert_eq!(pixel(&file, 0, (1, 0)), RED);
+    }
+
+    #[test]
+    fn linked_cel_uses_other_frame() {
+        let bytes = file(
+            (1, 1),
+            0,
+            &[
+                (
+                    100,
+                    vec![visible_layer("base"), raw_cel(0, (0, 0), 1, &[RED])],
+                ),
+                (100, vec![linked_cel(0, 0)]),
+            ],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        assert_eq!(pixel(&file, 1, (0, 0)), RED);
+    }
+
+    #[test]
+    fn tag_directions() {
+        let bytes = file(
+            (1, 1),
+            0,
+            &[
+                (
+                    100,
+                    vec![tags(&[
+                        ("idle", 0, 1, 0, 0),
+                        ("back", 1, 2, 1, 0),
+                        ("bounce", 0, 2, 2, 3),
+                        ("bounce_back", 2, 3, 3, 1),
+                    ])],
+                ),
+                (100, vec![]),
+                (100, vec![]),
+                (100, vec![]),
+            ],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        let tags: Vec<(&str, usize, usize, TagDirection, u16)> = file
+            .tags
+            .iter()
+            .map(|tag| {
+                (
+                    tag.name.as_str(),
+                    tag.from,
+                    tag.to,
+                    tag.direction,
+                    tag.repeat,
+                )
+            })
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("idle", 0, 1, TagDirection::Forward, 0),
+                ("back", 1, 2, TagDirection::Reverse, 0),
+                ("bounce", 0, 2, TagDirection::PingPong, 3),
+                ("bounce_back", 2, 3, TagDirection::PingPongReverse, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn layers_are_stacked_in_order() {
+        let bytes = file(
+            (1, 1),
+            0,
+            &[(
+                100,
+                vec![
+                    visible_layer("bottom"),
+                    visible_layer("top"),
+                    // Cels are drawn by layer, not in the order they are stored
+                    raw_cel(1, (0, 0), 1, &[BLUE]),
+                    raw_cel(0, (0, 0), 1, &[RED]),
+                ],
+            )],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        assert_eq!(pixel(&file, 0, (0, 0)), BLUE);
+    }
+
+    #[test]
+    fn hidden_layers_are_skipped() {
+        let bytes = file(
+            (3, 1),
+            0,
+            &[(
+                100,
+                vec![
+                    layer("hidden", 0, 0, 0, LAYER_BLEND_NORMAL, 255),
+                    layer(
+                        "reference",
+                        LAYER_FLAG_VISIBLE | LAYER_FLAG_REFERENCE,
+                        0,
+                        0,
+                        LAYER_BLEND_NORMAL,
+                        255,
+                    ),
+                    // A visible layer in a hidden group
+                    layer("group", 0, 1, 0, LAYER_BLEND_NORMAL, 255),
+                    layer("child", LAYER_FLAG_VISIBLE, 0, 1, LAYER_BLEND_NORMAL, 255),
+                    visible_layer("after_group"),
+                    raw_cel(0, (0, 0), 1, &[RED]),
+                    raw_cel(1, (1, 0), 1, &[RED]),
+                    raw_cel(3, (2, 0), 1, &[RED]),
+                    raw_cel(4, (0, 0), 1, &[BLUE]),
+                ],
+            )],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        assert_eq!(pixel(&file, 0, (0, 0)), BLUE);
+        assert_eq!(pixel(&file, 0, (1, 0)), CLEAR);
+        assert_eq!(pixel(&file, 0, (2, 0)), CLEAR);
+    }
+
+    #[test]
+    fn layer_opacity() {
+        let frames = [(
+            100,
+            vec![
+                layer("faded", LAYER_FLAG_VISIBLE, 0, 0, LAYER_BLEND_NORMAL, 51),
+                raw_cel(0, (0, 0), 1, &[RED]),
+            ],
+        )];
+        let faded = AsepriteFile::parse(&file((1, 1), HEADER_FLAG_LAYER_OPACITY, &frames)).unwrap();
+        assert_eq!(pixel(&faded, 0, (0, 0)), [255, 0, 0, 51]);
+        // Without the header flag, the opacity of layers isn't valid
+        let opaque = AsepriteFile::parse(&file((1, 1), 0, &frames)).unwrap();
+        assert_eq!(pixel(&opaque, 0, (0, 0)), RED);
+    }
+
+    #[test]
+    fn other_blend_modes_are_drawn_as_normal() {
+        let bytes = file(
+            (1, 1),
+            0,
+            &[(
+                100,
+                vec![
+                    visible_layer("bottom"),
+                    layer("multiply", LAYER_FLAG_VISIBLE, 0, 0, 1, 255),
+                    raw_cel(0, (0, 0), 1, &[RED]),
+                    raw_cel(1, (0, 0), 1, &[BLUE]),
+                ],
+            )],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+        assert_eq!(pixel(&file, 0, (0, 0)), BLUE);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let bytes = file(
+            (2, 2),
+            0,
+            &[
+                (
+                    100,
+                    vec![
+                        visible_layer("base"),
+                        raw_cel(0, (0, 0), 2, &[RED, BLUE]),
+                        tags(&[("idle", 0, 1, 0, 0)]),
+                    ],
+                ),
+                (100, vec![zlib_cel(0, (0, 0), 2, &[BLUE, RED])]),
+            ],
+        );
+        assert!(AsepriteFile::parse(&bytes).is_ok());
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(
+                    AsepriteFile::parse(&bytes[..len]),
+                    Err(AsepriteError::UnexpectedEnd)
+                ),
+                "{len} of {} bytes",
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn sprites_of_the_game() {
+        for name in ["player", "items", "default_tiles"] {
+            let path = format!(
+                "{}/assets/sprites/{name}.aseprite",
+                env!("CARGO_MANIFEST_DIR")
+            );
+            let file = AsepriteFile::parse(&std::fs::read(&path).unwrap()).unwrap();
+            assert!(!file.frames.is_empty(), "{name}");
+            assert_eq!(file.durations.len(), file.frames.len(), "{name}");
+            for frame in file.frames.iter() {
+                assert_eq!(
+                    frame.len(),
+                    (file.width * file.height * 4) as usize,
+                    "{name}"
+                );
+            }
+            for tag in file.tags.iter() {
+                assert!(
+                    tag.from.max(tag.to) < file.frames.len(),
+                    "{name}: {}",
+                    tag.name
+                );
+            }
+        }
+    }
+}
@@ -2,6 +2,8 @@ use crate::util::{move_towards_vec3, vec3_lerp};
 use bevy::{prelude::*, render::camera::WindowOrigin};
 use bevy_ecs_ldtk::prelude::*;
 
+use super::state::is_playing;
+
 pub struct GameCameraPlugin;
 
 #[derive(StageLabel)]
@@ -21,7 +23,10 @@ impl Plugin for GameCameraPlugin {
         app.register_type::<CameraFollow>()
             .register_type::<GameCamera>()
             .add_startup_system(camera_setup)
-            .add_system_to_stage(CameraStages::CameraUpdate, camera_follow);
+            .add_system_to_stage(
+                CameraStages::CameraUpdate,
+                camera_follow.with_run_criteria(is_playing),
+            );
     }
 }
 
@@ -73,6 +78,8 @@ impl CameraFollow {
 }
 
 pub const PIXEL_SCALE: f32 = 6.0;
+/// Width and height of the view, in pixels of the game
+pub const VIEW_SIZE: f32 = 128.0;
 
 fn camera_setup(mut commands: Commands) {
     commands.spawn((
@@ -0,0 +1,252 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    entity_instance::player::{move_player, Player, WorldlyQuery},
+    health::{DeathEvent, Health},
+    kinematic_actor::{KaFrozen, KaState},
+    ldtk::EntityInstanceAdded,
+    room::LevelBoundsIndex,
+    state::{is_playing, GameState},
+};
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Checkpoint>()
+            .add_system(checkpoint_setup)
+            .add_system(checkpoint_touch.with_run_criteria(is_playing))
+            .add_system(initial_respawn_point)
+            .add_system(kill_plane.with_run_criteria(is_playing))
+            .init_resource::<SpawnedLevels>()
+            .add_system(track_spawned_levels)
+            // Dead players wait on the game over screen, and respawn when it's left
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(player_respawn.after(track_spawned_levels)),
+            )
+            .add_system(release_awaiting_players.after(player_respawn));
+    }
+}
+
+/// How far below the bottom of the current room the player has to fall to die, in units
+pub const KILL_PLANE_MARGIN: f32 = 16.0;
+
+/// Where the player comes back after dying
+#[derive(Resource, Clone, Debug)]
+pub struct RespawnPoint {
+    pub level_iid: String,
+    pub position: Vec2,
+}
+
+/// Holds a respawned player in place with [`KaFrozen`] until its level has been spawned,
+/// so it doesn't fall through a room that has no colliders yet
+#[derive(Component, Debug)]
+pub struct AwaitingLevel {
+    pub level_iid: String,
+}
+
+/// Iids of the levels whose [`LevelEvent::Spawned`] has been sent, and that haven't been
+/// despawned since
+#[derive(Resource, Default, Debug)]
+struct SpawnedLevels(HashSet<String>);
+
+/// Sensor of a `CHECKPOINT` entity
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct Checkpoint;
+
+fn checkpoint_setup(mut commands: Commands, mut events: EventReader<EntityInstanceAdded>) {
+    for event in events
+        .iter()
+        .filter(|e| e.instance.identifier == "CHECKPOINT")
+    {
+        commands.entity(event.entity).with_children(|builder| {
+            builder.spawn((
+                TransformBundle::default(),
+                Checkpoint,
+                RigidBody::Fixed,
+                ActiveEvents::COLLISION_EVENTS,
+                ActiveCollisionTypes::KINEMATIC_STATIC,
+                Collider::cuboid(
+                    event.instance.width as f32 / 2.0,
+                    event.instance.height as f32 / 2.0,
+                ),
+                Sensor,
+            ));
+        });
+    }
+}
+
+/// Moves the respawn point to the checkpoints the player touches
+fn checkpoint_touch(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    player_query: Query<(), With<Player>>,
+    checkpoint_query: Query<&GlobalTransform, With<Checkpoint>>,
+    level_bounds: Res<LevelBoundsIndex>,
+) {
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            for (player_entity, checkpoint_entity) in [(*e1, *e2), (*e2, *e1)] {
+                if let (true, Ok(transform)) = (
+                    player_query.contains(player_entity),
+                    checkpoint_query.get(checkpoint_entity),
+                ) {
+                    let position = transform.translation().truncate();
+                    if let Some(level) = level_bounds.level_at(position) {
+                        commands.insert_resource(RespawnPoint {
+                            level_iid: level.iid.clone(),
+                            position,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Until a checkpoint is reached, the player respawns where it was first spawned
+fn initial_respawn_point(
+    mut commands: Commands,
+    respawn_point: Option<Res<RespawnPoint>>,
+    player_query: Query<&Parent, Added<Player>>,
+    worldly_query: Query<(&Transform, &Parent), With<Worldly>>,
+    global_query: Query<&GlobalTransform>,
+    level_bounds: Res<LevelBoundsIndex>,
+) {
+    if respawn_point.is_some() {
+        return;
+    }
+    for parent in player_query.iter() {
+        // The transforms of a just spawned player haven't been propagated yet,
+        // but the world's has
+        if let Ok((transform, world)) = worldly_query.get(parent.get()) {
+            let world_position = global_query
+                .get(world.get())
+                .map_or(Vec2::ZERO, |world_transform| {
+                    world_transform.translation().truncate()
+                });
+            let position = world_position + transform.translation.truncate();
+            if let Some(level) = level_bounds.level_at(position) {
+                commands.insert_resource(RespawnPoint {
+                    level_iid: level.iid.clone(),
+                    position,
+                });
+            }
+        }
+    }
+}
+
+/// Kills the player once it falls below the current room with no other room to fall into
+fn kill_plane(
+    mut player_query: Query<(Entity, &GlobalTransform, &mut Health), With<Player>>,
+    level_selection: Option<Res<LevelSelection>>,
+    level_bounds: Res<LevelBoundsIndex>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    let current =
+        match level_selection.and_then(|level_selection| level_bounds.selected(&level_selection)) {
+            Some(current) => current,
+            None => return,
+        };
+
+    for (entity, transform, mut health) in player_query.iter_mut() {
+        let position = transform.translation().truncate();
+        if health.is_dead()
+            || position.y >= current.rect.min.y - KILL_PLANE_MARGIN
+            || level_bounds.level_at(position).is_some()
+        {
+            continue;
+        }
+        health.current = 0;
+        death_events.send(DeathEvent { entity });
+    }
+}
+
+fn track_spawned_levels(
+    mut level_events: EventReader<LevelEvent>,
+    mut spawned_levels: ResMut<SpawnedLevels>,
+) {
+    for event in level_events.iter() {
+        match event {
+            LevelEvent::Spawned(iid) => {
+                spawned_levels.0.insert(iid.clone());
+            }
+            LevelEvent::SpawnTriggered(iid) | LevelEvent::Despawned(iid) => {
+                spawned_levels.0.remove(iid);
+            }
+            LevelEvent::Transformed(_) => {}
+        }
+    }
+}
+
+/// Brings dead players back at the [`RespawnPoint`] with full health, when the game over screen
+/// the death led to is left.
+///
+/// Players that respawn in a level that isn't spawned yet are held there with [`AwaitingLevel`].
+fn player_respawn(
+    mut commands: Commands,
+    mut player_query: Query<
+        (Entity, &Parent, &mut Transform, &mut Health, &mut KaState),
+        With<Player>,
+    >,
+    mut worldly_query: WorldlyQuery,
+    global_query: Query<&GlobalTransform>,
+    respawn_point: Option<Res<RespawnPoint>>,
+    mut level_selection: Option<ResMut<LevelSelection>>,
+    spawned_levels: Res<SpawnedLevels>,
+) {
+    for (entity, parent, mut transform, mut health, mut state) in player_query.iter_mut() {
+        if !health.is_dead() {
+            continue;
+        }
+        let respawn_point = match &respawn_point {
+            Some(respawn_point) => respawn_point,
+            None => {
+                warn!("Player died without a respawn point");
+                continue;
+            }
+        };
+
+        if move_player(
+            parent,
+            &mut transform,
+            respawn_point.position,
+            &mut worldly_query,
+            &global_query,
+        ) {
+            health.reset();
+            *state = KaState::default();
+            if let Some(level_selection) = level_selection.as_mut() {
+                **level_selection = LevelSelection::Iid(respawn_point.level_iid.clone());
+            }
+            if !spawned_levels.0.contains(&respawn_point.level_iid) {
+                commands.entity(entity).insert((
+                    AwaitingLevel {
+                        level_iid: respawn_point.level_iid.clone(),
+                    },
+                    KaFrozen,
+                ));
+            }
+        }
+    }
+}
+
+/// Lets respawned players move again once the level they are waiting for has been spawned
+fn release_awaiting_players(
+    mut commands: Commands,
+    player_query: Query<(Entity, &AwaitingLevel)>,
+    spawned_levels: Res<SpawnedLevels>,
+) {
+    for (entity, awaiting) in player_query.iter() {
+        if spawned_levels.0.contains(&awaiting.level_iid) {
+            commands
+                .entity(entity)
+                .remove::<AwaitingLevel>()
+                .remove::<KaFrozen>();
+        }
+    }
+}
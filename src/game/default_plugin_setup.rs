@@ -1,24 +1,68 @@
-use bevy::prelude::*;
+use bevy::{input::InputPlugin, prelude::*, text::FontLoader};
 
-use super::camera::PIXEL_SCALE;
+use super::camera::{PIXEL_SCALE, VIEW_SIZE};
 
-pub struct DefaultPluginSetup;
+pub struct DefaultPluginSetup {
+    pub window: WindowDescriptor,
+}
+
+impl Default for DefaultPluginSetup {
+    fn default() -> Self {
+        Self {
+            window: WindowDescriptor {
+                width: VIEW_SIZE * PIXEL_SCALE,
+                height: VIEW_SIZE * PIXEL_SCALE,
+                title: "Sigil".to_string(),
+                resizable: false,
+                ..default()
+            },
+        }
+    }
+}
 
 impl Plugin for DefaultPluginSetup {
     fn build(&self, app: &mut App) {
         app.insert_resource(ClearColor(Color::BLACK)).add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
-                    window: WindowDescriptor {
-                        width: 128.0 * PIXEL_SCALE,
-                        height: 128.0 * PIXEL_SCALE,
-                        title: "Sigil".to_string(),
-                        resizable: false,
-                        ..default()
-                    },
+                    window: self.window.clone(),
+                    ..default()
+                })
+                .set(AssetPlugin {
+                    // Hot reload levels, sprites and tuning files while developing
+                    watch_for_changes: cfg!(debug_assertions),
                     ..default()
                 })
                 .set(ImagePlugin::default_nearest()),
         );
     }
 }
+
+/// The parts of bevy the game needs without a window, audio, gamepads or rendering, for
+/// simulating the game in tests.
+///
+/// This is [`MinimalPlugins`] with asset loading, transforms and input. The assets of the render
+/// plugins that are left out are still registered, so sprites, texture atlases and fonts load and
+/// spawn like they do with a window, they just aren't drawn. LDtk and rapier are added by
+/// [`super::GamePlugins`].
+pub struct HeadlessPluginSetup;
+
+impl Plugin for HeadlessPluginSetup {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(WindowPlugin {
+                add_primary_window: false,
+                exit_on_all_closed: false,
+                ..default()
+            })
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(ImagePlugin::default_nearest())
+            .add_asset::<TextureAtlas>()
+            .add_asset::<Font>()
+            .init_asset_loader::<FontLoader>()
+            .init_resource::<ClearColor>();
+    }
+}
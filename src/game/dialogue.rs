@@ -0,0 +1,557 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use super::{
+    ability::{Abilities, Ability},
+    item::{Inventory, ItemId},
+    ldtk::WorldState,
+};
+
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Dialogue>()
+            .init_asset_loader::<DialogueLoader>();
+    }
+}
+
+/// How many nodes can be passed through without showing anything, before giving up on a loop
+const MAX_SKIPPED_NODES: usize = 64;
+
+/// Conversation loaded from a `.dialogue.ron` file, played by a [`DialogueRunner`].
+///
+/// ```ron
+/// (
+///     start: "hello",
+///     nodes: {
+///         "hello": (
+///             speaker: Some("Hermit"),
+///             text: "Are you looking for the way up?",
+///             choices: [
+///                 (text: "Yes", actions: [SetFlag("hermit_gate")], next: Some("thanks")),
+///                 (text: "No"),
+///             ],
+///         ),
+///         "thanks": (text: "The gate is open now."),
+///     },
+/// )
+/// ```
+#[derive(TypeUuid, Deserialize, Clone, Debug, Default)]
+#[uuid = "5d1c3a8e-2f47-4b9a-9e61-0c8b7d4f2a15"]
+pub struct Dialogue {
+    /// Node the dialogue starts from
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl Dialogue {
+    /// Checks that every node referred to exists
+    pub fn validate(&self) -> Result<(), String> {
+        let mut references = vec![("start", &self.start)];
+        for node in self.nodes.values() {
+            references.extend(node.next.iter().map(|next| ("next", next)));
+            references.extend(node.branches.iter().map(|branch| ("branch", &branch.next)));
+            references.extend(
+                node.choices
+                    .iter()
+                    .filter_map(|choice| choice.next.as_ref())
+                    .map(|next| ("choice", next)),
+            );
+        }
+        match references
+            .into_iter()
+            .find(|(_, id)| !self.nodes.contains_key(*id))
+        {
+            Some((kind, id)) => Err(format!("Unknown {kind} node: {id}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// One step of a [`Dialogue`]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DialogueNode {
+    pub speaker: Option<String>,
+    /// Empty lines separate pages. Nodes without text are passed through right away.
+    pub text: String,
+    /// Run when the node is entered
+    pub actions: Vec<DialogueAction>,
+    /// The first branch whose condition holds is followed right away, skipping the text
+    pub branches: Vec<DialogueBranch>,
+    /// Offered after the text, if any of their conditions hold
+    pub choices: Vec<DialogueChoice>,
+    /// Node after the text when there are no choices, `None` ends the dialogue
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DialogueBranch {
+    pub condition: Condition,
+    pub next: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DialogueChoice {
+    pub text: String,
+    /// The choice is hidden unless this holds
+    pub condition: Option<Condition>,
+    /// Run when the choice is picked
+    pub actions: Vec<DialogueAction>,
+    /// `None` ends the dialogue
+    pub next: Option<String>,
+}
+
+/// Items and abilities are named by their LDtk enum values, e.g. `Item("CROSS")`
+#[derive(Deserialize, Clone, Debug)]
+pub enum Condition {
+    Flag(String),
+    Item(String),
+    Ability(String),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn holds(&self, context: &impl DialogueContext) -> bool {
+        match self {
+            Self::Flag(flag) => context.has_flag(flag),
+            Self::Item(item) => context.has_item(item),
+            Self::Ability(ability) => context.has_ability(ability),
+            Self::Not(condition) => !condition.holds(context),
+            Self::All(conditions) => conditions.iter().all(|condition| condition.holds(context)),
+            Self::Any(conditions) => conditions.iter().any(|condition| condition.holds(context)),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum DialogueAction {
+    SetFlag(String),
+    ClearFlag(String),
+}
+
+impl DialogueAction {
+    pub fn run(&self, context: &mut impl DialogueContext) {
+        match self {
+            Self::SetFlag(flag) => context.set_flag(flag, true),
+            Self::ClearFlag(flag) => context.set_flag(flag, false),
+        }
+    }
+}
+
+/// The game state a [`Dialogue`] can check and change
+pub trait DialogueContext {
+    fn has_flag(&self, flag: &str) -> bool;
+    fn set_flag(&mut self, flag: &str, value: bool);
+    fn has_item(&self, item: &str) -> bool;
+    fn has_ability(&self, ability: &str) -> bool;
+}
+
+/// [`DialogueContext`] of the player and the world
+pub struct GameDialogueContext<'a> {
+    pub world_state: &'a mut WorldState,
+    pub inventory: Option<&'a Inventory>,
+    pub abilities: Option<&'a Abilities>,
+}
+
+impl DialogueContext for GameDialogueContext<'_> {
+    fn has_flag(&self, flag: &str) -> bool {
+        self.world_state.has_flag(flag)
+    }
+
+    fn set_flag(&mut self, flag: &str, value: bool) {
+        self.world_state.set_flag(flag, value);
+    }
+
+    fn has_item(&self, item: &str) -> bool {
+        match (ItemId::from_ldtk(item), self.inventory) {
+            (Some(item), Some(inventory)) => inventory.has(item),
+            _ => false,
+        }
+    }
+
+    fn has_ability(&self, ability: &str) -> bool {
+        match (Ability::from_ldtk(ability), self.abilities) {
+            (Some(ability), Some(abilities)) => abilities.has(ability),
+            _ => false,
+        }
+    }
+}
+
+/// Plays a [`Dialogue`], independent of how it is shown
+#[derive(Clone, Debug)]
+pub struct DialogueRunner {
+    dialogue: Dialogue,
+    /// Shown node, `None` once the dialogue has ended
+    node: Option<String>,
+    /// Indices of the choices of the node whose conditions held when it was entered
+    choices: Vec<usize>,
+}
+
+impl DialogueRunner {
+    pub fn start(dialogue: Dialogue, context: &mut impl DialogueContext) -> Self {
+        let start = dialogue.start.clone();
+        let mut runner = Self {
+            dialogue,
+            node: None,
+            choices: Vec::new(),
+        };
+        runner.enter(Some(start), context);
+        runner
+    }
+
+    pub fn current(&self) -> Option<&DialogueNode> {
+        self.node
+            .as_ref()
+            .and_then(|id| self.dialogue.nodes.get(id))
+    }
+
+    pub fn current_id(&self) -> Option<&str> {
+        self.node.as_deref()
+    }
+
+    /// Choices offered by the current node
+    pub fn choices(&self) -> Vec<&DialogueChoice> {
+        self.current().map_or(Vec::new(), |node| {
+            self.choices
+                .iter()
+                .map(|index| &node.choices[*index])
+                .collect()
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.node.is_none()
+    }
+
+    /// Leaves the current node, through the given index into [`Self::choices`] if there are any
+    pub fn advance(&mut self, choice: usize, context: &mut impl DialogueContext) {
+        let node = match self.current() {
+            Some(node) => node,
+            None => return,
+        };
+        let next = if self.choices.is_empty() {
+            node.next.clone()
+        } else {
+            let choice = &node.choices[self.choices[choice.min(self.choices.len() - 1)]];
+            for action in choice.actions.iter() {
+                action.run(context);
+            }
+            choice.next.clone()
+        };
+        self.enter(next, context);
+    }
+
+    /// Runs the actions of the node and follows branches and empty nodes until there's something to show
+    fn enter(&mut self, mut next: Option<String>, context: &mut impl DialogueContext) {
+        self.choices.clear();
+        for _ in 0..MAX_SKIPPED_NODES {
+            let id = match next.take() {
+                Some(id) => id,
+                None => break,
+            };
+            let node = match self.dialogue.nodes.get(&id) {
+                Some(node) => node,
+                None => {
+                    warn!("Unknown dialogue node: {id}");
+                    break;
+                }
+            };
+            for action in node.actions.iter() {
+                action.run(context);
+            }
+            if let Some(branch) = node
+                .branches
+                .iter()
+                .find(|branch| branch.condition.holds(context))
+            {
+                next = Some(branch.next.clone());
+                continue;
+            }
+
+            let choices: Vec<usize> = node
+                .choices
+                .iter()
+                .enumerate()
+                .filter(|(_, choice)| {
+                    choice
+                        .condition
+                        .as_ref()
+                        .map_or(true, |condition| condition.holds(context))
+                })
+                .map(|(index, _)| index)
+                .collect();
+            if node.text.trim().is_empty() && choices.is_empty() {
+                next = node.next.clone();
+                continue;
+            }
+            self.node = Some(id);
+            self.choices = choices;
+            return;
+        }
+        if let Some(id) = next {
+            warn!("Passed through {MAX_SKIPPED_NODES} dialogue nodes up to {id}, ending a loop");
+        }
+        self.node = None;
+    }
+}
+
+#[derive(Default)]
+pub struct DialogueLoader;
+
+impl AssetLoader for DialogueLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let dialogue: Dialogue = ron::de::from_bytes(bytes)?;
+            dialogue.validate().map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(dialogue));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dialogue.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[derive(Default)]
+    struct MockContext {
+        flags: HashSet<String>,
+        items: HashSet<String>,
+        abilities: HashSet<String>,
+    }
+
+    impl DialogueContext for MockContext {
+        fn has_flag(&self, flag: &str) -> bool {
+            self.flags.contains(flag)
+        }
+
+        fn set_flag(&mut self, flag: &str, value: bool) {
+            if value {
+                self.flags.insert(flag.to_string());
+            } else {
+                self.flags.remove(flag);
+            }
+        }
+
+        fn has_item(&self, item: &str) -> bool {
+            self.items.contains(item)
+        }
+
+        fn has_ability(&self, ability: &str) -> bool {
+            self.abilities.contains(ability)
+        }
+    }
+
+    fn dialogue(text: &str) -> Dialogue {
+        let dialogue: Dialogue = ron::de::from_str(text).unwrap();
+        dialogue.validate().unwrap();
+        dialogue
+    }
+
+    fn choice_texts(runner: &DialogueRunner) -> Vec<&str> {
+        runner
+            .choices()
+            .iter()
+            .map(|choice| choice.text.as_str())
+            .collect()
+    }
+
+    const HERMIT: &str = r#"(
+        start: "hello",
+        nodes: {
+            "hello": (
+                branches: [(condition: Flag("hermit_gate"), next: "again")],
+                next: Some("greeting"),
+            ),
+            "greeting": (
+                speaker: Some("Hermit"),
+                text: "Are you looking for the way up?",
+                choices: [
+                    (text: "Yes", actions: [SetFlag("hermit_gate")], next: Some("thanks")),
+                    (text: "Show the cross", condition: Some(Item("CROSS")), next: Some("cross")),
+                    (text: "No"),
+                ],
+            ),
+            "thanks": (text: "The gate is open now."),
+            "cross": (text: "Where did you find that?", actions: [SetFlag("seen_cross")]),
+            "again": (text: "The gate is still open."),
+        },
+    )"#;
+
+    #[test]
+    fn branch_selection() {
+        let mut context = MockContext::default();
+        let runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert_eq!(runner.current_id(), Some("greeting"));
+
+        context.set_flag("hermit_gate", true);
+        let runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert_eq!(runner.current_id(), Some("again"));
+    }
+
+    #[test]
+    fn hidden_choices() {
+        let mut context = MockContext::default();
+        let runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert_eq!(choice_texts(&runner), vec!["Yes", "No"]);
+
+        context.items.insert("CROSS".to_string());
+        let mut runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert_eq!(choice_texts(&runner), vec!["Yes", "Show the cross", "No"]);
+
+        // Choices are picked by their index among the visible ones
+        runner.advance(1, &mut context);
+        assert_eq!(runner.current_id(), Some("cross"));
+    }
+
+    #[test]
+    fn choice_actions_run_when_picked() {
+        let mut context = MockContext::default();
+        let mut runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert!(!context.has_flag("hermit_gate"));
+
+        runner.advance(0, &mut context);
+        assert!(context.has_flag("hermit_gate"));
+        assert_eq!(runner.current_id(), Some("thanks"));
+
+        runner.advance(0, &mut context);
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn picking_no_choice_ends_the_dialogue() {
+        let mut context = MockContext::default();
+        let mut runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        runner.advance(1, &mut context);
+        assert!(runner.is_finished());
+        assert!(context.flags.is_empty());
+    }
+
+    #[test]
+    fn node_actions_run_on_entry() {
+        let mut context = MockContext::default();
+        context.items.insert("CROSS".to_string());
+        let mut runner = DialogueRunner::start(dialogue(HERMIT), &mut context);
+        assert!(!context.has_flag("seen_cross"));
+
+        runner.advance(1, &mut context);
+        // Before the text of the node is shown
+        assert_eq!(runner.current_id(), Some("cross"));
+        assert!(context.has_flag("seen_cross"));
+    }
+
+    #[test]
+    fn empty_nodes_are_passed_through() {
+        let mut context = MockContext::default();
+        let mut runner = DialogueRunner::start(
+            dialogue(
+                r#"(
+                    start: "first",
+                    nodes: {
+                        "first": (text: "Hello", next: Some("empty")),
+                        "empty": (actions: [SetFlag("passed")], next: Some("last")),
+                        "last": (text: "Bye"),
+                    },
+                )"#,
+            ),
+            &mut context,
+        );
+        runner.advance(0, &mut context);
+        assert_eq!(runner.current_id(), Some("last"));
+        assert!(context.has_flag("passed"));
+    }
+
+    #[test]
+    fn ability_conditions() {
+        let text = r#"(
+            start: "start",
+            nodes: {
+                "start": (
+                    branches: [
+                        (condition: All([Ability("DASH"), Not(Flag("told"))]), next: "dash"),
+                        (condition: Any([Ability("DASH"), Ability("WALL_JUMP")]), next: "other"),
+                    ],
+                    next: Some("nothing"),
+                ),
+                "dash": (text: "You can dash!"),
+                "other": (text: "You know that already."),
+                "nothing": (text: "Nothing to say."),
+            },
+        )"#;
+        let mut context = MockContext::default();
+        let runner = DialogueRunner::start(dialogue(text), &mut context);
+        assert_eq!(runner.current_id(), Some("nothing"));
+
+        context.abilities.insert("DASH".to_string());
+        let runner = DialogueRunner::start(dialogue(text), &mut context);
+        assert_eq!(runner.current_id(), Some("dash"));
+
+        context.set_flag("told", true);
+        let runner = DialogueRunner::start(dialogue(text), &mut context);
+        assert_eq!(runner.current_id(), Some("other"));
+    }
+
+    #[test]
+    fn loops_of_empty_nodes_end() {
+        let mut context = MockContext::default();
+        let runner = DialogueRunner::start(
+            dialogue(
+                r#"(
+                    start: "ping",
+                    nodes: {
+                        "ping": (actions: [SetFlag("pinged")], next: Some("pong")),
+                        "pong": (next: Some("ping")),
+                    },
+                )"#,
+            ),
+            &mut context,
+        );
+        assert!(runner.is_finished());
+        assert!(context.has_flag("pinged"));
+    }
+
+    #[test]
+    fn validate_rejects_unknown_nodes() {
+        let validate = |text: &str| ron::de::from_str::<Dialogue>(text).unwrap().validate();
+        assert!(validate(r#"(start: "a", nodes: {"a": (text: "A")})"#).is_ok());
+        assert_eq!(
+            validate(r#"(start: "missing", nodes: {"a": (text: "A")})"#),
+            Err("Unknown start node: missing".to_string())
+        );
+        assert_eq!(
+            validate(r#"(start: "a", nodes: {"a": (next: Some("missing"))})"#),
+            Err("Unknown next node: missing".to_string())
+        );
+        assert_eq!(
+            validate(
+                r#"(start: "a", nodes: {"a": (branches: [(condition: Flag("f"), next: "missing")])})"#
+            ),
+            Err("Unknown branch node: missing".to_string())
+        );
+        assert_eq!(
+            validate(
+                r#"(start: "a", nodes: {"a": (choices: [(text: "Go", next: Some("missing"))])})"#
+            ),
+            Err("Unknown choice node: missing".to_string())
+        );
+    }
+}
@@ -1,13 +1,38 @@
+pub mod enemy;
+pub mod gate;
+pub mod npc;
 pub mod pickup;
 pub mod player;
 
 use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::state::is_playing;
 
 pub struct EntityInstancePlugin;
 
 impl Plugin for EntityInstancePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(player::PlayerPlugin)
-            .add_system(pickup::pickup_setup);
+        app.register_type::<gate::Gate>()
+            .register_type::<gate::GateSensor>()
+            .register_type::<enemy::Enemy>()
+            .add_plugin(player::PlayerPlugin)
+            .add_system(pickup::pickup_setup)
+            .add_system(gate::gate_setup)
+            .add_system(gate::gate_update.with_run_criteria(is_playing))
+            .add_system(npc::npc_setup)
+            .add_system(enemy::enemy_setup);
     }
 }
+
+/// Value of a string field, `None` if it's null or empty
+pub(crate) fn string_field(instance: &EntityInstance, identifier: &str) -> Option<String> {
+    instance
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .and_then(|field| match &field.value {
+            FieldValue::String(Some(value)) if !value.is_empty() => Some(value.clone()),
+            _ => None,
+        })
+}
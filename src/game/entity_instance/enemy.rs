@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::string_field;
+use crate::game::{
+    health::Hazard,
+    kinematic_actor::*,
+    ldtk::EntityInstanceAdded,
+    navigation::{NavAgent, NavChasePlayer},
+};
+
+/// Movement profile of enemies without a `MOVEMENT_PROFILE` field
+pub const ENEMY_MOVEMENT_PROFILE: &str = "enemy";
+
+/// Damage dealt to the player on contact
+pub const ENEMY_DAMAGE: u32 = 1;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Enemy;
+
+#[derive(Bundle, Default)]
+pub struct EnemyBundle {
+    enemy: Enemy,
+    platformer: Platformer,
+    profile: KaProfile,
+    nav_agent: NavAgent,
+    chase: NavChasePlayer,
+    hazard: Hazard,
+    collider: Collider,
+    // The player walks through enemies and gets hurt instead
+    sensor: Sensor,
+    kinematic_actor: KinematicActorBundle,
+}
+
+pub fn enemy_setup(mut commands: Commands, mut events: EventReader<EntityInstanceAdded>) {
+    for event in events.iter().filter(|e| e.instance.identifier == "ENEMY") {
+        let profile = string_field(&event.instance, "MOVEMENT_PROFILE")
+            .unwrap_or_else(|| ENEMY_MOVEMENT_PROFILE.to_string());
+
+        commands.entity(event.entity).with_children(|builder| {
+            builder
+                .spawn(EnemyBundle {
+                    profile: KaProfile::new(&profile),
+                    hazard: Hazard {
+                        damage: ENEMY_DAMAGE,
+                    },
+                    collider: Collider::cuboid(3.0, 3.0),
+                    ..default()
+                })
+                .with_children(|enemy| {
+                    // There's no enemy art yet
+                    enemy.spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgb(0.89, 0.23, 0.27),
+                            custom_size: Some(Vec2::new(6.0, 6.0)),
+                            ..default()
+                        },
+                        ..default()
+                    });
+                });
+        });
+    }
+}
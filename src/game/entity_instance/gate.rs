@@ -0,0 +1,235 @@
+use super::string_field;
+use crate::game::{ability::*, entity_instance::player::Player, ldtk::*};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// How far from the gate the player can open it, in units
+const GATE_SENSOR_MARGIN: f32 = 2.0;
+
+/// Blocks the way until the player touches it with the required ability and the required flag set
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct Gate {
+    pub required: Option<Ability>,
+    pub required_flag: Option<String>,
+}
+
+impl Gate {
+    pub fn opens(&self, abilities: &Abilities, world_state: &WorldState) -> bool {
+        self.required.map_or(true, |ability| abilities.has(ability))
+            && self
+                .required_flag
+                .as_ref()
+                .map_or(true, |flag| world_state.has_flag(flag))
+    }
+}
+
+/// Sensor slightly larger than its [`Gate`], a child of the LDtk entity like the gate's collider
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct GateSensor;
+
+pub fn gate_setup(
+    mut commands: Commands,
+    mut events: EventReader<EntityInstanceAdded>,
+    transform_query: Query<&Transform>,
+) {
+    for event in events.iter().filter(|e| e.instance.identifier == "GATE") {
+        let required = event
+            .instance
+            .field_instances
+            .iter()
+            .find(|field| field.identifier == "REQUIRED_ABILITY")
+            .and_then(|field| match &field.value {
+                FieldValue::Enum(Some(id)) => Ability::from_ldtk(id),
+                _ => None,
+            });
+        let required_flag = string_field(&event.instance, "REQUIRED_FLAG");
+        if required.is_none() && required_flag.is_none() {
+            warn!(
+                "GATE without a valid REQUIRED_ABILITY or REQUIRED_FLAG: {}",
+                event.instance.iid
+            );
+            continue;
+        }
+
+        // Resized entities are scaled, so size the children in the unscaled space
+        let scale = transform_query
+            .get(event.entity)
+            .map_or(Vec3::ONE, |transform| transform.scale)
+            .truncate();
+        let size = Vec2::new(event.instance.width as f32, event.instance.height as f32) / scale;
+
+        commands
+            .entity(event.entity)
+            .insert(Gate {
+                required,
+                required_flag,
+            })
+            .with_children(|builder| {
+                builder.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgb(0.4, 0.35, 0.5),
+                            custom_size: Some(size),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    RigidBody::Fixed,
+                    Collider::cuboid(size.x / 2.0, size.y / 2.0),
+                    Friction::new(1.0),
+                ));
+                builder.spawn((
+                    TransformBundle::default(),
+                    GateSensor,
+                    RigidBody::Fixed,
+                    ActiveCollisionTypes::KINEMATIC_STATIC,
+                    Collider::cuboid(
+                        size.x / 2.0 + GATE_SENSOR_MARGIN,
+                        size.y / 2.0 + GATE_SENSOR_MARGIN,
+                    ),
+                    Sensor,
+                ));
+            });
+    }
+}
+
+/// Opens the gates the player touches with the ability and flag for them. Opened gates stay open.
+///
+/// Contact is checked every frame instead of on [`CollisionEvent::Started`], so a gate also opens
+/// when the player unlocks it while already touching it.
+pub fn gate_update(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    sensor_query: Query<(Entity, &Parent), With<GateSensor>>,
+    gate_query: Query<(&Gate, &EntityInstance)>,
+    player_query: Query<&Abilities, With<Player>>,
+    mut world_state: ResMut<WorldState>,
+) {
+    for (sensor, parent) in sensor_query.iter() {
+        let (gate, instance) = match gate_query.get(parent.get()) {
+            Ok(gate) => gate,
+            Err(_) => continue,
+        };
+        let opened = rapier_context
+            .intersections_with(sensor)
+            .any(|(e1, e2, intersecting)| {
+                let other = if e1 == sensor { e2 } else { e1 };
+                intersecting
+                    && player_query
+                        .get(other)
+                        .map_or(false, |abilities| gate.opens(abilities, &world_state))
+            });
+        if opened {
+            commands.entity(parent.get()).despawn_recursive();
+            world_state.remove(&instance.iid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .init_resource::<WorldState>()
+            .add_system(gate_update);
+        app
+    }
+
+    fn spawn_gate(world: &mut World) -> Entity {
+        world
+            .spawn((
+                Gate {
+                    required: Some(Ability::Dash),
+                    required_flag: None,
+                },
+                EntityInstance {
+                    iid: "gate".to_string(),
+                    ..default()
+                },
+                TransformBundle::default(),
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    TransformBundle::default(),
+                    GateSensor,
+                    RigidBody::Fixed,
+                    ActiveCollisionTypes::KINEMATIC_STATIC,
+                    Collider::cuboid(4.0 + GATE_SENSOR_MARGIN, 8.0 + GATE_SENSOR_MARGIN),
+                    Sensor,
+                ));
+            })
+            .id()
+    }
+
+    fn spawn_player(world: &mut World, x: f32) -> Entity {
+        let mut abilities = Abilities::default();
+        abilities.unlock(Ability::Dash);
+        world
+            .spawn((
+                Player,
+                abilities,
+                RigidBody::KinematicPositionBased,
+                Collider::cuboid(3.0, 3.0),
+                TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0)),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn gates_open_on_contact() {
+        let mut app = app();
+        let gate = spawn_gate(&mut app.world);
+        let player = spawn_player(&mut app.world, 40.0);
+        for _ in 0..3 {
+            app.update();
+        }
+        // Having the ability anywhere in the world isn't enough
+        assert!(app.world.get_entity(gate).is_some());
+
+        app.world
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation
+            .x = 8.0;
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(app.world.get_entity(gate).is_none());
+        assert!(app.world.resource::<WorldState>().is_removed("gate"));
+    }
+
+    #[test]
+    fn gates_stay_closed_without_the_ability() {
+        let mut app = app();
+        let gate = spawn_gate(&mut app.world);
+        let player = spawn_player(&mut app.world, 8.0);
+        app.world
+            .get_mut::<Abilities>(player)
+            .unwrap()
+            .unlocked
+            .clear();
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(app.world.get_entity(gate).is_some());
+
+        // Unlocking the ability while touching the gate opens it
+        app.world
+            .get_mut::<Abilities>(player)
+            .unwrap()
+            .unlock(Ability::Dash);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(app.world.get_entity(gate).is_none());
+    }
+}
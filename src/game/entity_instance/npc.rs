@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::string_field;
+use crate::game::{interaction::Interactable, ldtk::EntityInstanceAdded};
+
+pub fn npc_setup(
+    mut commands: Commands,
+    mut events: EventReader<EntityInstanceAdded>,
+    assets: Res<AssetServer>,
+) {
+    for event in events.iter().filter(|e| e.instance.identifier == "NPC") {
+        let speaker = string_field(&event.instance, "NAME");
+        let text = string_field(&event.instance, "TEXT").unwrap_or_default();
+        let dialogue = string_field(&event.instance, "DIALOGUE").map(|path| assets.load(path));
+
+        commands.entity(event.entity).with_children(|builder| {
+            builder.spawn((
+                // There's no character art yet
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(0.88, 0.63, 0.25),
+                        custom_size: Some(Vec2::new(6.0, 8.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Interactable {
+                    prompt: "Talk".to_string(),
+                    speaker,
+                    text,
+                    dialogue,
+                },
+                RigidBody::Fixed,
+                ActiveCollisionTypes::KINEMATIC_STATIC,
+                Collider::cuboid(6.0, 4.0),
+                Sensor,
+            ));
+        });
+    }
+}
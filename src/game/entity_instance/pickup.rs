@@ -1,4 +1,5 @@
-use crate::game::ldtk::*;
+use super::string_field;
+use crate::game::{interaction::Interactable, item::*, ldtk::*};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
@@ -12,6 +13,9 @@ pub fn pickup_setup(
         .iter()
         .filter(|e| e.instance.identifier == "ITEM_PICKUP")
     {
+        let text = string_field(&event.instance, "TEXT");
+        let required_flag = string_field(&event.instance, "REQUIRED_FLAG");
+
         commands.entity(event.entity).with_children(|builder| {
             for field in event.instance.field_instances.iter() {
                 match field.identifier.as_str() {
@@ -20,23 +24,53 @@ pub fn pickup_setup(
                             FieldValue::Enum(value) => value,
                             _ => &None,
                         } {
-                            let value_def = ldtk_enum.items.get(id).unwrap();
+                            let item = match ItemId::from_ldtk(id) {
+                                Some(item) => item,
+                                None => {
+                                    warn!("Unknown ITEM_ID: {id}");
+                                    continue;
+                                }
+                            };
+                            let tile_id = match ldtk_enum
+                                .items
+                                .get(id)
+                                .and_then(|value_def| value_def.tile_id)
+                            {
+                                Some(tile_id) => tile_id,
+                                None => {
+                                    warn!("ITEM_ID {id} has no tile in the LDtk enum");
+                                    continue;
+                                }
+                            };
 
-                            builder.spawn((
+                            let mut pickup = builder.spawn((
                                 SpriteSheetBundle {
                                     texture_atlas: ldtk_enum.item_atlas.clone(),
-                                    sprite: TextureAtlasSprite::new(
-                                        value_def.tile_id.unwrap() as usize
-                                    ),
+                                    sprite: TextureAtlasSprite::new(tile_id as usize),
+                                    visibility: Visibility {
+                                        is_visible: required_flag.is_none(),
+                                    },
                                     ..default()
                                 },
                                 Name::new(id.clone()),
+                                Pickup {
+                                    item,
+                                    required_flag: required_flag.clone(),
+                                },
                                 RigidBody::Fixed,
                                 ActiveEvents::COLLISION_EVENTS,
                                 ActiveCollisionTypes::KINEMATIC_STATIC,
                                 Collider::cuboid(3.5, 3.5),
                                 Sensor,
                             ));
+                            if let Some(text) = &text {
+                                pickup.insert(Interactable {
+                                    prompt: "Read".to_string(),
+                                    speaker: None,
+                                    text: text.clone(),
+                                    dialogue: None,
+                                });
+                            }
                         }
                     }
                     _ => (),
@@ -3,7 +3,20 @@ use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
-    game::{camera::CameraFollow, kinematic_actor::*, ldtk::EntityInstanceAdded},
+    game::{
+        ability::Abilities,
+        animation::{AnimationClip, AnimationSystem, SpriteAnimation},
+        aseprite::Aseprite,
+        camera::CameraFollow,
+        health::{Health, PLAYER_BASE_HEALTH},
+        interaction::ActiveDialogue,
+        item::Inventory,
+        kinematic_actor::*,
+        ldtk::EntityInstanceAdded,
+        loading::RequiredAssets,
+        room::{LevelBoundsIndex, RoomDirection, RoomTransitionEvent, ROOM_TRANSITION_MARGIN},
+        state::is_playing,
+    },
     util::axis_from_digital,
 };
 
@@ -12,12 +25,35 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Player>()
+            .add_startup_system(preload_player_sprite)
             .add_system(player_spawner)
-            .add_system(update_level_selection)
-            .add_system_to_stage(CoreStage::PreUpdate, player_input.after(InputSystem));
+            .add_system(update_level_selection.with_run_criteria(is_playing))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                player_animation
+                    .after(KaPhysicsSystem)
+                    .before(AnimationSystem)
+                    .with_run_criteria(is_playing),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                player_input
+                    .label(PlayerInputSystem)
+                    .after(InputSystem)
+                    .with_run_criteria(is_playing),
+            );
     }
 }
 
+/// Sets the [`KaInput`] of the player from the keyboard
+#[derive(Debug, PartialEq, Eq, Clone, Hash, SystemLabel)]
+pub struct PlayerInputSystem;
+
+pub const PLAYER_SPRITE_PATH: &str = "sprites/player.aseprite";
+
+/// Movement profile of players without a `MOVEMENT_PROFILE` field
+pub const PLAYER_MOVEMENT_PROFILE: &str = "player";
+
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct Player;
@@ -25,48 +61,67 @@ pub struct Player;
 #[derive(Bundle, Default)]
 pub struct PlayerBundle {
     player: Player,
+    inventory: Inventory,
+    abilities: Abilities,
+    health: Health,
     platformer: Platformer,
+    profile: KaProfile,
     collider: Collider,
     ccd: Ccd,
     sleeping: Sleeping,
     kinematic_actor: KinematicActorBundle,
 }
 
+/// The player is spawned with the level, so make sure its sprite has loaded by then
+fn preload_player_sprite(assets: Res<AssetServer>, mut required: ResMut<RequiredAssets>) {
+    let aseprite: Handle<Aseprite> = assets.load(PLAYER_SPRITE_PATH);
+    required.add(&aseprite);
+}
+
 fn player_spawner(
     mut commands: Commands,
     mut events: EventReader<EntityInstanceAdded>,
     assets: Res<AssetServer>,
-    mut atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     for event in events.iter().filter(|e| e.instance.identifier == "PLAYER") {
+        let profile = event
+            .instance
+            .field_instances
+            .iter()
+            .find(|field| field.identifier == "MOVEMENT_PROFILE")
+            .and_then(|field| match &field.value {
+                FieldValue::String(Some(name)) if !name.is_empty() => Some(name.as_str()),
+                _ => None,
+            })
+            .unwrap_or(PLAYER_MOVEMENT_PROFILE);
+
         commands
             .entity(event.entity)
-            .insert(Worldly::default())
+            .insert(Worldly::from_entity_info(&event.instance))
             .with_children(|builder| {
-                let atlas = TextureAtlas::from_grid(
-                    assets.load("sprites/export/player.png"),
-                    Vec2::splat(8.0),
-                    3,
-                    1,
-                    Some(Vec2::splat(2.0)),
-                    Some(Vec2::splat(0.0)),
-                );
-                let atlas_handle = atlases.add(atlas);
+                let aseprite: Handle<Aseprite> = assets.load(PLAYER_SPRITE_PATH);
+                let atlas_handle = assets.load(format!("{PLAYER_SPRITE_PATH}#atlas"));
 
                 builder
                     .spawn(PlayerBundle {
+                        health: Health::new(PLAYER_BASE_HEALTH),
+                        profile: KaProfile::new(profile),
                         collider: Collider::cuboid(3.0, 3.0),
                         ccd: Ccd::enabled(),
                         sleeping: Sleeping::disabled(),
                         ..default()
                     })
                     .with_children(|player| {
-                        player.spawn(SpriteSheetBundle {
-                            transform: Transform::from_xyz(0.0, 1.0, 0.0),
-                            texture_atlas: atlas_handle.clone(),
-                            sprite: TextureAtlasSprite::new(0),
-                            ..default()
-                        });
+                        player.spawn((
+                            SpriteSheetBundle {
+                                transform: Transform::from_xyz(0.0, 1.0, 0.0),
+                                texture_atlas: atlas_handle.clone(),
+                                sprite: TextureAtlasSprite::new(0),
+                                ..default()
+                            },
+                            player_animations(),
+                            aseprite.clone(),
+                        ));
                         player.spawn((
                             TransformBundle::from_transform(Transform::from_xyz(0.0, -32.0, 0.0)),
                             CameraFollow::instant(0),
@@ -76,42 +131,169 @@ fn player_spawner(
     }
 }
 
+/// Animation states of the player, each with a clip of the same name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayerAnimationState {
+    Idle,
+    Run,
+    Jump,
+    Fall,
+}
+
+impl PlayerAnimationState {
+    /// Horizontal speed above which the player counts as running
+    const RUN_THRESHOLD: f32 = 5.0;
+
+    pub fn from_state(state: &KaState) -> Self {
+        if state.on_ground {
+            if state.velocity.x.abs() > Self::RUN_THRESHOLD {
+                Self::Run
+            } else {
+                Self::Idle
+            }
+        } else if state.velocity.y > 0.0 {
+            Self::Jump
+        } else {
+            Self::Fall
+        }
+    }
+
+    pub fn clip_name(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Run => "run",
+            Self::Jump => "jump",
+            Self::Fall => "fall",
+        }
+    }
+}
+
+/// Clips of the 3-frame player atlas. Tags of the same name in player.aseprite replace these.
+fn player_animations() -> SpriteAnimation {
+    SpriteAnimation::default()
+        .with_clip(
+            PlayerAnimationState::Idle.clip_name(),
+            AnimationClip::still(0),
+        )
+        .with_clip(
+            PlayerAnimationState::Run.clip_name(),
+            AnimationClip::looping(0, 1, 8.0),
+        )
+        .with_clip(
+            PlayerAnimationState::Jump.clip_name(),
+            AnimationClip::still(2),
+        )
+        .with_clip(
+            PlayerAnimationState::Fall.clip_name(),
+            AnimationClip::still(2),
+        )
+}
+
+/// Picks the player's animation clip from its movement, and faces the sprite the way it's moving
+fn player_animation(
+    player_query: Query<(&KaState, &KaInput, &Children), With<Player>>,
+    mut sprite_query: Query<(&mut SpriteAnimation, &mut TextureAtlasSprite)>,
+) {
+    for (state, input, children) in player_query.iter() {
+        let animation_state = PlayerAnimationState::from_state(state);
+        for child in children.iter() {
+            if let Ok((mut animation, mut sprite)) = sprite_query.get_mut(*child) {
+                animation.play(animation_state.clip_name());
+                if input.movement.x != 0.0 {
+                    sprite.flip_x = input.movement.x < 0.0;
+                }
+            }
+        }
+    }
+}
+
+/// [`Worldly`] LDtk entities, which the players are children of
+pub type WorldlyQuery<'w, 's> =
+    Query<'w, 's, (&'static mut Transform, &'static Parent), (With<Worldly>, Without<Player>)>;
+
+/// Moves the player to the given world position.
+///
+/// The player is a child of its [`Worldly`] LDtk entity, which is a child of the LDtk world,
+/// so the worldly entity is moved relative to the world. The player moves away from the worldly
+/// entity as it walks, so it's put back on top of it.
+/// Returns false if the player isn't attached to a worldly entity.
+pub fn move_player(
+    player_parent: &Parent,
+    player_transform: &mut Transform,
+    position: Vec2,
+    worldly_query: &mut WorldlyQuery,
+    global_query: &Query<&GlobalTransform>,
+) -> bool {
+    match worldly_query.get_mut(player_parent.get()) {
+        Ok((mut transform, world)) => {
+            let world_position = global_query
+                .get(world.get())
+                .map_or(Vec2::ZERO, |world_transform| {
+                    world_transform.translation().truncate()
+                });
+            transform.translation = (position - world_position).extend(transform.translation.z);
+            player_transform.translation = Vec2::ZERO.extend(player_transform.translation.z);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Selects the room the player is in.
+///
+/// The current room is kept until the player is [`ROOM_TRANSITION_MARGIN`] units outside of it,
+/// and a [`RoomTransitionEvent`] is sent whenever the room changes.
 pub fn update_level_selection(
-    level_query: Query<(&Handle<LdtkLevel>, &Transform), Without<Player>>,
     player_query: Query<&GlobalTransform, With<Player>>,
     mut level_selection: ResMut<LevelSelection>,
-    ldtk_levels: Res<Assets<LdtkLevel>>,
+    level_bounds: Res<LevelBoundsIndex>,
+    mut transition_events: EventWriter<RoomTransitionEvent>,
 ) {
     for player_transform in &player_query {
-        for (level_handle, level_transform) in &level_query {
-            if let Some(ldtk_level) = ldtk_levels.get(level_handle) {
-                let level_bounds = Rect {
-                    min: Vec2::new(level_transform.translation.x, level_transform.translation.y),
-                    max: Vec2::new(
-                        level_transform.translation.x + ldtk_level.level.px_wid as f32,
-                        level_transform.translation.y + ldtk_level.level.px_hei as f32,
-                    ),
-                };
-
-                if player_transform.translation().x < level_bounds.max.x
-                    && player_transform.translation().x > level_bounds.min.x
-                    && player_transform.translation().y < level_bounds.max.y
-                    && player_transform.translation().y > level_bounds.min.y
-                    && !level_selection.is_match(&0, &ldtk_level.level)
-                {
-                    *level_selection = LevelSelection::Iid(ldtk_level.level.iid.clone());
-                }
+        let position = player_transform.translation().truncate();
+        let current = level_bounds.selected(&level_selection);
+
+        if current.map_or(false, |current| {
+            current
+                .rect
+                .inset(ROOM_TRANSITION_MARGIN)
+                .contains(position)
+        }) {
+            continue;
+        }
+
+        if let Some(next) = level_bounds.level_at(position) {
+            if current.map_or(true, |current| current.iid != next.iid) {
+                transition_events.send(RoomTransitionEvent {
+                    from: current.map(|current| current.iid.clone()),
+                    to: next.iid.clone(),
+                    direction: current
+                        .and_then(|current| RoomDirection::from_exit(&current.rect, position)),
+                });
+                *level_selection = LevelSelection::Iid(next.iid.clone());
             }
         }
     }
 }
 
-fn player_input(input: Res<Input<KeyCode>>, mut query: Query<&mut KaInput, With<Player>>) {
+/// The player stands still while a dialogue is open, only interacting to advance it
+fn player_input(
+    input: Res<Input<KeyCode>>,
+    dialogue: Option<Res<ActiveDialogue>>,
+    mut query: Query<&mut KaInput, With<Player>>,
+) {
+    let can_move = dialogue.is_none();
     for mut ka_input in query.iter_mut() {
         ka_input.movement = Vec2 {
-            x: axis_from_digital(input.pressed(KeyCode::Left), input.pressed(KeyCode::Right)),
+            x: if can_move {
+                axis_from_digital(input.pressed(KeyCode::Left), input.pressed(KeyCode::Right))
+            } else {
+                0.0
+            },
             y: 0.0,
         };
-        ka_input.jump.set(input.pressed(KeyCode::C));
+        ka_input.jump.set(can_move && input.pressed(KeyCode::C));
+        ka_input.dash.set(can_move && input.pressed(KeyCode::X));
+        ka_input.interact.set(input.pressed(KeyCode::Up));
     }
 }
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    ability::{Abilities, Ability},
+    kinematic_actor::{KaPhysicsSystem, KaState},
+    state::is_playing,
+};
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .register_type::<Hazard>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_system(hazard_contact.with_run_criteria(is_playing))
+            .add_system(
+                apply_damage
+                    .after(hazard_contact)
+                    .with_run_criteria(is_playing),
+            )
+            .add_system(max_health_from_abilities)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                invulnerability_update
+                    .after(KaPhysicsSystem)
+                    .with_run_criteria(is_playing),
+            );
+    }
+}
+
+/// Health the player starts with
+pub const PLAYER_BASE_HEALTH: u32 = 3;
+/// Health added by every unlocked [`Ability::MaxHealth`]
+pub const HEALTH_PER_MAX_HEALTH: u32 = 1;
+/// Velocity an actor is knocked back with when hurt
+pub const KNOCKBACK_VELOCITY: Vec2 = Vec2::new(60.0, 80.0);
+
+#[derive(Reflect, Component, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Health {
+    pub max: u32,
+    pub current: u32,
+    /// How long the entity can't be hurt again after taking damage, in seconds
+    pub invulnerability_duration: f32,
+    invulnerability_timer: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            current: max,
+            invulnerability_duration: 1.0,
+            invulnerability_timer: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerability_timer > 0.0
+    }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    /// Restores full health and clears the invulnerability, e.g. when respawning
+    pub fn reset(&mut self) {
+        self.current = self.max;
+        self.invulnerability_timer = 0.0;
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Sensor that hurts anything with [`Health`] touching it
+#[derive(Reflect, Component, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Hazard {
+    pub damage: u32,
+}
+
+/// Sent to hurt an entity with [`Health`]
+#[derive(Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    /// Velocity the target is knocked back with, if it's a kinematic actor
+    pub knockback: Vec2,
+}
+
+/// Sent when an entity's [`Health`] runs out
+#[derive(Clone, Copy, Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+}
+
+/// Knockback pushing the target away from the source horizontally, and always a bit up
+pub fn knockback_from(source: Vec2, target: Vec2) -> Vec2 {
+    let direction = if target.x < source.x { -1.0 } else { 1.0 };
+    Vec2::new(direction * KNOCKBACK_VELOCITY.x, KNOCKBACK_VELOCITY.y)
+}
+
+/// Hurts everything standing in a [`Hazard`].
+///
+/// Checks the ongoing intersections instead of collision events,
+/// so staying in a hazard keeps hurting once the invulnerability runs out.
+fn hazard_contact(
+    rapier_context: Res<RapierContext>,
+    target_query: Query<(Entity, &GlobalTransform, &Health)>,
+    hazard_query: Query<(&Hazard, &GlobalTransform)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (entity, transform, health) in target_query.iter() {
+        if health.is_invulnerable() || health.is_dead() {
+            continue;
+        }
+        for (e1, e2, intersecting) in rapier_context.intersections_with(entity) {
+            let other = if e1 == entity { e2 } else { e1 };
+            if let (true, Ok((hazard, hazard_transform))) = (intersecting, hazard_query.get(other))
+            {
+                damage_events.send(DamageEvent {
+                    target: entity,
+                    amount: hazard.damage,
+                    knockback: knockback_from(
+                        hazard_transform.translation().truncate(),
+                        transform.translation().truncate(),
+                    ),
+                });
+                // One hazard at a time is enough
+                break;
+            }
+        }
+    }
+}
+
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<(&mut Health, Option<&mut KaState>)>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for event in damage_events.iter() {
+        if let Ok((mut health, state)) = health_query.get_mut(event.target) {
+            if health.is_invulnerable() || health.is_dead() {
+                continue;
+            }
+            health.current = health.current.saturating_sub(event.amount);
+            health.invulnerability_timer = health.invulnerability_duration;
+
+            if let Some(mut state) = state {
+                state.velocity = event.knockback;
+                // Don't snap back to the ground when knocked upwards
+                if event.knockback.y > 0.0 {
+                    state.is_jumping = true;
+                }
+            }
+
+            if health.is_dead() {
+                death_events.send(DeathEvent {
+                    entity: event.target,
+                });
+            }
+        }
+    }
+}
+
+fn invulnerability_update(mut query: Query<&mut Health>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut health in query.iter_mut() {
+        if health.is_invulnerable() {
+            health.invulnerability_timer -= dt;
+        }
+    }
+}
+
+/// Maximum health of the player with the given abilities
+pub fn max_health(abilities: &Abilities) -> u32 {
+    PLAYER_BASE_HEALTH + abilities.count(Ability::MaxHealth) * HEALTH_PER_MAX_HEALTH
+}
+
+/// Every unlocked [`Ability::MaxHealth`] raises the maximum health and fills it up
+fn max_health_from_abilities(mut query: Query<(&Abilities, &mut Health), Changed<Abilities>>) {
+    for (abilities, mut health) in query.iter_mut() {
+        let max = max_health(abilities);
+        if max > health.max {
+            health.max = max;
+            health.current = max;
+        } else if max < health.max {
+            health.max = max;
+            health.current = health.current.min(max);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::{
+    camera::{GameCamera, PIXEL_SCALE, VIEW_SIZE},
+    entity_instance::player::Player,
+    health::Health,
+    item::{Inventory, ItemId},
+    ldtk::LdtkEnum,
+    menu::UiFont,
+    room::LevelBoundsIndex,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        // The camera and the font are set up in startup systems
+        app.add_startup_system_to_stage(StartupStage::PostStartup, hud_setup)
+            .add_system(hud_health)
+            .add_system(hud_items)
+            .add_system(hud_room);
+    }
+}
+
+/// Depth of the HUD relative to the camera, in front of the whole world
+const HUD_DEPTH: f32 = 0.5;
+
+const PIP_SIZE: f32 = 4.0;
+const PIP_SPACING: f32 = 1.0;
+const PIP_COLOR: Color = Color::rgb(0.85, 0.15, 0.2);
+const EMPTY_PIP_COLOR: Color = Color::rgb(0.25, 0.2, 0.25);
+
+const ITEM_ICON_SIZE: f32 = 8.0;
+const ITEM_ICON_SPACING: f32 = 1.0;
+
+/// Minimap pixels per world pixel, a 128x128 room is 4x4 pixels
+const MINIMAP_SCALE: f32 = 1.0 / 32.0;
+/// Area of the world shown around the current room, in minimap pixels
+const MINIMAP_SIZE: Vec2 = Vec2::new(32.0, 16.0);
+const MINIMAP_ROOM_COLOR: Color = Color::rgb(0.35, 0.35, 0.45);
+const MINIMAP_CURRENT_ROOM_COLOR: Color = Color::WHITE;
+
+const MARGIN: f32 = 2.0;
+const ROOM_NAME_FONT_SIZE: f32 = 5.0;
+
+/// Parent of the HUD sprites. A child of the [`GameCamera`], positioned in pixels of the game
+/// so the HUD lines up with the pixels of the world.
+#[derive(Component)]
+pub struct HudRoot;
+
+#[derive(Component)]
+struct HealthPip;
+
+#[derive(Component)]
+struct ItemIcon;
+
+#[derive(Component)]
+struct MinimapRoom;
+
+#[derive(Component)]
+struct RoomName;
+
+/// Translation of a HUD element, from the position of its top left corner relative to the top
+/// left corner of the view, y going down
+fn hud_translation(position: Vec2, size: Vec2) -> Vec3 {
+    Vec3::new(
+        -VIEW_SIZE / 2.0 + position.x + size.x / 2.0,
+        VIEW_SIZE / 2.0 - position.y - size.y / 2.0,
+        0.0,
+    )
+}
+
+fn hud_rect(position: Vec2, size: Vec2, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform::from_translation(hud_translation(position, size)),
+        ..default()
+    }
+}
+
+fn hud_setup(
+    mut commands: Commands,
+    camera_query: Query<Entity, With<GameCamera>>,
+    font: Res<UiFont>,
+) {
+    for camera in camera_query.iter() {
+        commands
+            .entity(camera)
+            // Without visibility on the camera, its children would never be visible
+            .insert(VisibilityBundle::default())
+            .with_children(|builder| {
+                builder.spawn((
+                    Name::new("HUD"),
+                    SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, -HUD_DEPTH)),
+                    HudRoot,
+                ));
+            });
+    }
+
+    // Sprite text would be blurry when scaled up with the view, so the room name is UI text
+    // positioned on the pixel grid instead
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font.0.clone(),
+                font_size: ROOM_NAME_FONT_SIZE * PIXEL_SCALE,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px((MARGIN * 2.0 + MINIMAP_SIZE.y) * PIXEL_SCALE),
+                right: Val::Px(MARGIN * PIXEL_SCALE),
+                ..default()
+            },
+            ..default()
+        }),
+        RoomName,
+    ));
+}
+
+/// One pip per point of maximum health, filled up to the current health
+fn hud_health(
+    mut commands: Commands,
+    root_query: Query<Entity, With<HudRoot>>,
+    pip_query: Query<Entity, With<HealthPip>>,
+    player_query: Query<&Health, With<Player>>,
+    mut shown: Local<Option<(u32, u32)>>,
+) {
+    let health = player_query
+        .iter()
+        .next()
+        .map(|health| (health.current, health.max));
+    if *shown == health {
+        return;
+    }
+    *shown = health;
+
+    for entity in pip_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (current, max) = match health {
+        Some(health) => health,
+        None => return,
+    };
+    for root in root_query.iter() {
+        commands.entity(root).with_children(|builder| {
+            for i in 0..max {
+                let position = Vec2::new(MARGIN + i as f32 * (PIP_SIZE + PIP_SPACING), MARGIN);
+                let color = if i < current {
+                    PIP_COLOR
+                } else {
+                    EMPTY_PIP_COLOR
+                };
+                builder.spawn((hud_rect(position, Vec2::splat(PIP_SIZE), color), HealthPip));
+            }
+        });
+    }
+}
+
+/// An icon for every collected item, below the health
+fn hud_items(
+    mut commands: Commands,
+    root_query: Query<Entity, With<HudRoot>>,
+    icon_query: Query<Entity, With<ItemIcon>>,
+    player_query: Query<&Inventory, With<Player>>,
+    ldtk_enum: Res<LdtkEnum>,
+    mut shown: Local<Vec<ItemId>>,
+) {
+    let items: Vec<ItemId> = player_query.iter().next().map_or(Vec::new(), |inventory| {
+        ItemId::ALL
+            .into_iter()
+            .filter(|item| item.is_collectible())
+            .flat_map(|item| (0..inventory.count(item)).map(move |_| item))
+            .collect()
+    });
+    if *shown == items && !ldtk_enum.is_changed() {
+        return;
+    }
+    *shown = items;
+
+    for entity in icon_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for root in root_query.iter() {
+        commands.entity(root).with_children(|builder| {
+            for (i, item) in shown.iter().enumerate() {
+                let tile_id = match ldtk_enum
+                    .items
+                    .get(item.ldtk_id())
+                    .and_then(|value_def| value_def.tile_id)
+                {
+                    Some(tile_id) => tile_id,
+                    None => continue,
+                };
+                let size = Vec2::splat(ITEM_ICON_SIZE);
+                let position = Vec2::new(
+                    MARGIN + i as f32 * (ITEM_ICON_SIZE + ITEM_ICON_SPACING),
+                    MARGIN * 2.0 + PIP_SIZE,
+                );
+                builder.spawn((
+                    SpriteSheetBundle {
+                        texture_atlas: ldtk_enum.item_atlas.clone(),
+                        sprite: TextureAtlasSprite {
+                            index: tile_id as usize,
+                            custom_size: Some(size),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(hud_translation(position, size)),
+                        ..default()
+                    },
+                    ItemIcon,
+                ));
+            }
+        });
+    }
+}
+
+/// Shows the name of the current room, and the rooms around it on the minimap
+fn hud_room(
+    mut commands: Commands,
+    root_query: Query<Entity, With<HudRoot>>,
+    minimap_query: Query<Entity, With<MinimapRoom>>,
+    mut name_query: Query<&mut Text, With<RoomName>>,
+    level_selection: Option<Res<LevelSelection>>,
+    index: Res<LevelBoundsIndex>,
+) {
+    let level_selection = match level_selection {
+        Some(level_selection) => level_selection,
+        None => return,
+    };
+    if !level_selection.is_changed() && !index.is_changed() {
+        return;
+    }
+    let current = index.selected(&level_selection);
+
+    for mut text in name_query.iter_mut() {
+        text.sections[0].value = current.map_or(String::new(), |level| level.identifier.clone());
+    }
+
+    for entity in minimap_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let current = match current {
+        Some(current) => current,
+        None => return,
+    };
+    // Whole minimap pixels, with the current room in the middle
+    let center = (current.rect.center() * MINIMAP_SCALE).floor();
+    let view = Rect::from_center_size(center, MINIMAP_SIZE);
+    let view = Rect::from_corners(view.min.floor(), view.max.floor());
+    let origin = Vec2::new(VIEW_SIZE - MARGIN - MINIMAP_SIZE.x, MARGIN);
+
+    for root in root_query.iter() {
+        commands.entity(root).with_children(|builder| {
+            builder.spawn((
+                hud_rect(
+                    origin - Vec2::ONE,
+                    MINIMAP_SIZE + Vec2::splat(2.0),
+                    Color::rgba(0.0, 0.0, 0.0, 0.6),
+                ),
+                MinimapRoom,
+            ));
+            for level in index.levels.iter() {
+                // Leave a pixel between neighbouring rooms
+                let room = Rect::from_corners(
+                    (level.rect.min * MINIMAP_SCALE).floor(),
+                    (level.rect.max * MINIMAP_SCALE).floor() - Vec2::ONE,
+                );
+                let visible = room.intersect(view);
+                if visible.is_empty() {
+                    continue;
+                }
+                let color = if level.iid == current.iid {
+                    MINIMAP_CURRENT_ROOM_COLOR
+                } else {
+                    MINIMAP_ROOM_COLOR
+                };
+                // The world goes up, the HUD goes down
+                let position =
+                    origin + Vec2::new(visible.min.x - view.min.x, view.max.y - visible.max.y);
+                let mut rect = hud_rect(position, visible.size(), color);
+                rect.transform.translation.z = 0.1;
+                builder.spawn((rect, MinimapRoom));
+            }
+        });
+    }
+}
@@ -0,0 +1,430 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    ability::Abilities,
+    dialogue::{Dialogue, DialogueRunner, GameDialogueContext},
+    entity_instance::player::Player,
+    item::Inventory,
+    kinematic_actor::KaInput,
+    ldtk::WorldState,
+    menu::UiFont,
+    state::{is_playing, GameState},
+};
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Interactable>()
+            .init_resource::<NearbyInteractable>()
+            // The font is loaded in a startup system
+            .add_startup_system_to_stage(StartupStage::PostStartup, interaction_ui_setup)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(is_playing)
+                    .with_system(interaction_detect)
+                    .with_system(dialogue_choose)
+                    .with_system(dialogue_advance.after(dialogue_choose))
+                    .with_system(interaction_start.after(interaction_detect))
+                    .with_system(prompt_update.after(interaction_start))
+                    .with_system(
+                        dialogue_box_update
+                            .after(interaction_start)
+                            .after(dialogue_advance),
+                    ),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(prompt_hide));
+    }
+}
+
+/// Something the player can read or talk to by pressing interact while touching its sensor
+#[derive(Reflect, Component, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct Interactable {
+    /// Shown when the player can interact, e.g. "Read"
+    pub prompt: String,
+    /// Name shown above the text, if any
+    pub speaker: Option<String>,
+    /// Empty lines separate the pages of the text
+    pub text: String,
+    /// Script played instead of the text once it has loaded
+    pub dialogue: Option<Handle<Dialogue>>,
+}
+
+impl Interactable {
+    pub fn pages(&self) -> Vec<String> {
+        split_pages(&self.text)
+    }
+}
+
+/// The [`Interactable`] the player is touching
+#[derive(Resource, Default, Debug)]
+pub struct NearbyInteractable(pub Option<Entity>);
+
+/// Dialogue being shown. The player can't move while it's open.
+#[derive(Resource, Debug)]
+pub struct ActiveDialogue {
+    pub speaker: Option<String>,
+    pub pages: Vec<String>,
+    pub page: usize,
+    /// Decides what comes after the last page, when playing a [`Dialogue`]
+    pub script: Option<DialogueRunner>,
+    /// Selected index into the choices of the script
+    pub choice: usize,
+}
+
+impl ActiveDialogue {
+    pub fn from_text(speaker: Option<String>, text: &str) -> Option<Self> {
+        let pages = split_pages(text);
+        (!pages.is_empty()).then_some(Self {
+            speaker,
+            pages,
+            page: 0,
+            script: None,
+            choice: 0,
+        })
+    }
+
+    /// `None` if the script has nothing to show
+    pub fn from_script(script: DialogueRunner) -> Option<Self> {
+        let mut dialogue = Self {
+            speaker: None,
+            pages: Vec::new(),
+            page: 0,
+            script: Some(script),
+            choice: 0,
+        };
+        dialogue.show_script_node();
+        (!dialogue.pages.is_empty()).then_some(dialogue)
+    }
+
+    pub fn current_page(&self) -> Option<&str> {
+        self.pages.get(self.page).map(|page| page.as_str())
+    }
+
+    pub fn is_last_page(&self) -> bool {
+        self.page + 1 >= self.pages.len()
+    }
+
+    /// Choices to pick from, shown with the last page
+    pub fn choices(&self) -> Vec<&str> {
+        match &self.script {
+            Some(script) if self.is_last_page() => script
+                .choices()
+                .into_iter()
+                .map(|choice| choice.text.as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn show_script_node(&mut self) {
+        let node = self.script.as_ref().and_then(|script| script.current());
+        self.speaker = node.and_then(|node| node.speaker.clone());
+        self.pages = node.map_or(Vec::new(), |node| split_pages(&node.text));
+        // Nodes with only choices still need a page to show them on
+        if self.pages.is_empty() && node.is_some() {
+            self.pages.push(String::new());
+        }
+        self.page = 0;
+        self.choice = 0;
+    }
+}
+
+fn split_pages(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|page| page.trim().to_string())
+        .filter(|page| !page.is_empty())
+        .collect()
+}
+
+#[derive(Component)]
+struct InteractPrompt;
+
+#[derive(Component)]
+struct DialogueBox;
+
+/// Text of the dialogue box
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum DialogueLine {
+    Speaker,
+    Page,
+    Choices,
+}
+
+/// Input of the players, and what dialogue conditions are checked against
+type DialoguePlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static KaInput,
+        Option<&'static Inventory>,
+        Option<&'static Abilities>,
+    ),
+    With<Player>,
+>;
+
+fn interaction_detect(
+    rapier_context: Res<RapierContext>,
+    player_query: Query<Entity, With<Player>>,
+    interactable_query: Query<(), With<Interactable>>,
+    mut nearby: ResMut<NearbyInteractable>,
+) {
+    let found = player_query.iter().find_map(|player| {
+        rapier_context
+            .intersections_with(player)
+            .find_map(|(e1, e2, intersecting)| {
+                let other = if e1 == player { e2 } else { e1 };
+                (intersecting && interactable_query.contains(other)).then_some(other)
+            })
+    });
+    if nearby.0 != found {
+        nearby.0 = found;
+    }
+}
+
+/// Opens the dialogue of the nearby [`Interactable`].
+/// The dialogue is inserted at the end of the stage, so it isn't advanced by the same press.
+fn interaction_start(
+    mut commands: Commands,
+    dialogue: Option<Res<ActiveDialogue>>,
+    nearby: Res<NearbyInteractable>,
+    player_query: DialoguePlayerQuery,
+    interactable_query: Query<&Interactable>,
+    dialogues: Res<Assets<Dialogue>>,
+    mut world_state: ResMut<WorldState>,
+) {
+    if dialogue.is_some() {
+        return;
+    }
+    let (inventory, abilities) = match player_query
+        .iter()
+        .find(|(input, ..)| input.interact.just_pressed())
+    {
+        Some((_, inventory, abilities)) => (inventory, abilities),
+        None => return,
+    };
+    let interactable = match nearby
+        .0
+        .and_then(|entity| interactable_query.get(entity).ok())
+    {
+        Some(interactable) => interactable,
+        None => return,
+    };
+
+    let script = interactable
+        .dialogue
+        .as_ref()
+        .and_then(|handle| dialogues.get(handle));
+    let dialogue = match script {
+        Some(script) => {
+            let mut context = GameDialogueContext {
+                world_state: &mut world_state,
+                inventory,
+                abilities,
+            };
+            ActiveDialogue::from_script(DialogueRunner::start(script.clone(), &mut context))
+        }
+        None => ActiveDialogue::from_text(interactable.speaker.clone(), &interactable.text),
+    };
+    if let Some(dialogue) = dialogue {
+        commands.insert_resource(dialogue);
+    }
+}
+
+/// Left and right select between the choices
+fn dialogue_choose(dialogue: Option<ResMut<ActiveDialogue>>, input: Res<Input<KeyCode>>) {
+    let mut dialogue = match dialogue {
+        Some(dialogue) => dialogue,
+        None => return,
+    };
+    let count = dialogue.choices().len();
+    if count == 0 {
+        return;
+    }
+    if input.just_pressed(KeyCode::Left) {
+        dialogue.choice = (dialogue.choice + count - 1) % count;
+    }
+    if input.just_pressed(KeyCode::Right) {
+        dialogue.choice = (dialogue.choice + 1) % count;
+    }
+}
+
+/// Interacting again shows the next page. After the last one the script continues with the
+/// selected choice, or the dialogue is closed.
+fn dialogue_advance(
+    mut commands: Commands,
+    dialogue: Option<ResMut<ActiveDialogue>>,
+    player_query: DialoguePlayerQuery,
+    mut world_state: ResMut<WorldState>,
+) {
+    let mut dialogue = match dialogue {
+        Some(dialogue) => dialogue,
+        None => return,
+    };
+    let (inventory, abilities) = match player_query
+        .iter()
+        .find(|(input, ..)| input.interact.just_pressed())
+    {
+        Some((_, inventory, abilities)) => (inventory, abilities),
+        None => return,
+    };
+
+    if !dialogue.is_last_page() {
+        dialogue.page += 1;
+        return;
+    }
+    let dialogue = &mut *dialogue;
+    if let Some(script) = &mut dialogue.script {
+        let mut context = GameDialogueContext {
+            world_state: &mut world_state,
+            inventory,
+            abilities,
+        };
+        script.advance(dialogue.choice, &mut context);
+        if !script.is_finished() {
+            dialogue.show_script_node();
+            return;
+        }
+    }
+    commands.remove_resource::<ActiveDialogue>();
+}
+
+fn interaction_ui_setup(mut commands: Commands, font: Res<UiFont>) {
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((
+        TextBundle::from_section("", text_style.clone()).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(16.0),
+                left: Val::Px(16.0),
+                ..default()
+            },
+            ..default()
+        }),
+        InteractPrompt,
+    ));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        bottom: Val::Px(16.0),
+                        left: Val::Px(16.0),
+                        right: Val::Px(16.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(12.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                ..default()
+            },
+            DialogueBox,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        color: Color::GOLD,
+                        ..text_style.clone()
+                    },
+                ),
+                DialogueLine::Speaker,
+            ));
+            builder.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                DialogueLine::Page,
+            ));
+            builder.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        color: Color::GRAY,
+                        ..text_style
+                    },
+                ),
+                DialogueLine::Choices,
+            ));
+        });
+}
+
+fn prompt_update(
+    dialogue: Option<Res<ActiveDialogue>>,
+    nearby: Res<NearbyInteractable>,
+    interactable_query: Query<&Interactable>,
+    mut prompt_query: Query<&mut Text, With<InteractPrompt>>,
+) {
+    let prompt = match (&dialogue, nearby.0) {
+        (None, Some(entity)) => interactable_query
+            .get(entity)
+            .map_or(String::new(), |interactable| {
+                format!("[Up] {}", interactable.prompt)
+            }),
+        _ => String::new(),
+    };
+    for mut text in prompt_query.iter_mut() {
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.clone();
+        }
+    }
+}
+
+fn dialogue_box_update(
+    dialogue: Option<Res<ActiveDialogue>>,
+    mut box_query: Query<&mut Style, With<DialogueBox>>,
+    mut line_query: Query<(&mut Text, &DialogueLine)>,
+) {
+    let display = if dialogue.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    for mut style in box_query.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    if let Some(dialogue) = dialogue.filter(|dialogue| dialogue.is_changed()) {
+        for (mut text, line) in line_query.iter_mut() {
+            text.sections[0].value = match line {
+                DialogueLine::Speaker => dialogue.speaker.clone().unwrap_or_default(),
+                DialogueLine::Page => dialogue.current_page().unwrap_or_default().to_string(),
+                DialogueLine::Choices => choices_text(&dialogue),
+            };
+        }
+    }
+}
+
+/// The selected choice is marked, e.g. `> Yes   No`
+fn choices_text(dialogue: &ActiveDialogue) -> String {
+    dialogue
+        .choices()
+        .iter()
+        .enumerate()
+        .map(|(index, choice)| {
+            let marker = if index == dialogue.choice { '>' } else { ' ' };
+            format!("{marker} {choice}")
+        })
+        .collect::<Vec<_>>()
+        .join("   ")
+}
+
+/// Menus cover the game, the prompt would show through them
+fn prompt_hide(mut prompt_query: Query<&mut Text, With<InteractPrompt>>) {
+    for mut text in prompt_query.iter_mut() {
+        text.sections[0].value.clear();
+    }
+}
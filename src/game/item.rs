@@ -0,0 +1,200 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use std::collections::HashSet;
+
+use super::{entity_instance::player::Player, ldtk::WorldState, state::is_playing};
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ItemId>()
+            .register_type::<Pickup>()
+            .register_type::<Inventory>()
+            .add_event::<ItemCollected>()
+            .add_system(pickup_collection.with_run_criteria(is_playing))
+            .add_system(pickup_visibility);
+    }
+}
+
+/// Values of the `ITEM_ID` enum in LDtk
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ItemId {
+    #[default]
+    Sign,
+    MaxHpTank,
+    Cross,
+    Sigma,
+}
+
+impl ItemId {
+    pub const ALL: [ItemId; 4] = [Self::Sign, Self::MaxHpTank, Self::Cross, Self::Sigma];
+
+    pub fn from_ldtk(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|item| item.ldtk_id() == id)
+    }
+
+    /// Enum value identifier in LDtk
+    pub fn ldtk_id(&self) -> &'static str {
+        match self {
+            Self::Sign => "SIGN",
+            Self::MaxHpTank => "MAX_HP_TANK",
+            Self::Cross => "CROSS",
+            Self::Sigma => "SIGMA",
+        }
+    }
+
+    /// Is the item picked up by touching it? Signs stay where they are.
+    pub fn is_collectible(&self) -> bool {
+        !matches!(self, Self::Sign)
+    }
+}
+
+/// Sent when the player collects an item
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ItemCollected(pub ItemId);
+
+/// Sensor of an `ITEM_PICKUP` entity
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct Pickup {
+    pub item: ItemId,
+    /// World flag the pickup is hidden and can't be collected without
+    pub required_flag: Option<String>,
+}
+
+impl Pickup {
+    pub fn is_available(&self, world_state: &WorldState) -> bool {
+        self.required_flag
+            .as_ref()
+            .map_or(true, |flag| world_state.has_flag(flag))
+    }
+}
+
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct Inventory {
+    pub items: HashMap<ItemId, u32>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: ItemId) {
+        *self.items.entry(item).or_default() += 1;
+    }
+
+    pub fn count(&self, item: ItemId) -> u32 {
+        self.items.get(&item).copied().unwrap_or(0)
+    }
+
+    pub fn has(&self, item: ItemId) -> bool {
+        self.count(item) > 0
+    }
+}
+
+/// Collects pickups the player touches.
+///
+/// The pickup sensor is a child of the LDtk entity, so the whole entity is despawned
+/// and marked as removed in the [`WorldState`].
+fn pickup_collection(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut player_query: Query<&mut Inventory, With<Player>>,
+    pickup_query: Query<(&Pickup, &Parent)>,
+    instance_query: Query<&EntityInstance>,
+    mut world_state: ResMut<WorldState>,
+    mut collected_events: EventWriter<ItemCollected>,
+) {
+    let mut collected = HashSet::new();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            for (player_entity, pickup_entity) in [(*e1, *e2), (*e2, *e1)] {
+                if let (Ok(mut inventory), Ok((pickup, parent))) = (
+                    player_query.get_mut(player_entity),
+                    pickup_query.get(pickup_entity),
+                ) {
+                    // Despawning happens at the end of the stage, don't collect twice meanwhile
+                    if pickup.item.is_collectible()
+                        && pickup.is_available(&world_state)
+                        && collected.insert(parent.get())
+                    {
+                        commands.entity(parent.get()).despawn_recursive();
+                        if let Ok(instance) = instance_query.get(parent.get()) {
+                            world_state.remove(&instance.iid);
+                        }
+                        inventory.add(pickup.item);
+                        collected_events.send(ItemCollected(pickup.item));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Shows pickups once their flag has been set, and hides new pickups until then
+fn pickup_visibility(
+    world_state: Res<WorldState>,
+    mut query: Query<(&Pickup, &mut Visibility, ChangeTrackers<Pickup>)>,
+) {
+    for (pickup, mut visibility, trackers) in query.iter_mut() {
+        if !world_state.is_changed() && !trackers.is_added() {
+            continue;
+        }
+        let available = pickup.is_available(&world_state);
+        if visibility.is_visible != available {
+            visibility.is_visible = available;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_pickup(world: &mut World, required_flag: &str) -> Entity {
+        world
+            .spawn((
+                Pickup {
+                    item: ItemId::Cross,
+                    required_flag: Some(required_flag.to_string()),
+                },
+                // Pickups with a flag are spawned hidden
+                Visibility { is_visible: false },
+            ))
+            .id()
+    }
+
+    #[test]
+    fn pickups_show_once_their_flag_is_set() {
+        let mut app = App::new();
+        app.init_resource::<WorldState>()
+            .add_system(pickup_visibility);
+        let pickup = spawn_pickup(&mut app.world, "shown");
+        app.update();
+        assert!(!app.world.get::<Visibility>(pickup).unwrap().is_visible);
+
+        app.world
+            .resource_mut::<WorldState>()
+            .set_flag("shown", true);
+        app.update();
+        assert!(app.world.get::<Visibility>(pickup).unwrap().is_visible);
+    }
+
+    #[test]
+    fn pickups_spawned_after_their_flag_are_visible() {
+        let mut app = App::new();
+        app.init_resource::<WorldState>()
+            .add_system(pickup_visibility);
+        app.world
+            .resource_mut::<WorldState>()
+            .set_flag("shown", true);
+        app.update();
+
+        // The level of the pickup spawns once the flag has long been set
+        let pickup = spawn_pickup(&mut app.world, "shown");
+        let hidden = spawn_pickup(&mut app.world, "not set");
+        app.update();
+        assert!(app.world.get::<Visibility>(pickup).unwrap().is_visible);
+        assert!(!app.world.get::<Visibility>(hidden).unwrap().is_visible);
+    }
+}
@@ -1,14 +1,17 @@
 use std::f32::consts::PI;
 
+use super::state::is_playing;
 use crate::util::*;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 mod input;
 mod platformer;
+mod profile;
 
 pub use input::*;
 pub use platformer::*;
+pub use profile::*;
 
 pub const GRAVITY_DIR: Vec2 = Vec2::NEG_Y;
 pub const GRAVITY_COEFFICIENT: f32 = 200.0;
@@ -23,13 +26,22 @@ impl Plugin for KinematicActorPlugin {
             .register_type::<KaProperties>()
             .register_type::<KaState>()
             .register_type::<KaType>()
+            .register_type::<KaProfile>()
+            .add_asset::<MovementProfiles>()
+            .init_asset_loader::<MovementProfilesLoader>()
+            .add_startup_system(load_movement_profiles)
+            .add_system(apply_movement_profiles)
             .add_system_to_stage(
                 CoreStage::PostUpdate,
-                kinematic_movement.label(KaPhysicsSystem),
+                kinematic_movement
+                    .label(KaPhysicsSystem)
+                    .with_run_criteria(is_playing),
             )
             .add_system_to_stage(
                 CoreStage::PostUpdate,
-                platformer_system.before(KaPhysicsSystem),
+                platformer_system
+                    .before(KaPhysicsSystem)
+                    .with_run_criteria(is_playing),
             );
     }
 }
@@ -80,6 +92,20 @@ pub struct KaProperties {
     pub air_acceleration_mod: f32,
     pub air_friction_mod: f32,
     pub jump_height: f32,
+    /// Gravity multiplier added at the start of a short hop (linearly interpolated).
+    /// Short hop gravity is added after normal gravity,
+    /// so treat a multiplier of 0 as normal gravity and 1 as double.
+    pub short_hop_gravity_mult_start: f32,
+    /// Gravity multiplier added at the end of a short hop (linearly interpolated)
+    pub short_hop_gravity_mult_stop: f32,
+    /// Dash speed relative to the normal movement speed
+    pub dash_speed_mult: f32,
+    /// How long a dash lasts, in seconds
+    pub dash_duration: f32,
+    /// How many extra jumps can be done in the air before landing
+    pub air_jumps: u32,
+    /// Can the actor jump off walls while in the air?
+    pub wall_jump: bool,
 }
 
 impl KaProperties {
@@ -107,6 +133,12 @@ impl Default for KaProperties {
             air_acceleration_mod: 1.0,
             air_friction_mod: 1.0,
             jump_height: 2.1,
+            short_hop_gravity_mult_start: 3.0,
+            short_hop_gravity_mult_stop: 0.5,
+            dash_speed_mult: 3.0,
+            dash_duration: 0.15,
+            air_jumps: 0,
+            wall_jump: false,
         }
     }
 }
@@ -118,14 +150,32 @@ pub struct KaState {
     pub velocity: Vec2,
     pub on_ground: bool,
     pub is_jumping: bool,
+    pub air_jumps_used: u32,
+    /// Horizontal direction pointing away from the wall the actor is touching in the air, 0 if none
+    pub wall_normal: f32,
+    /// Set while dashing, the velocity is kept as it is without gravity or acceleration
+    pub is_dashing: bool,
 }
 
 impl KaState {
     pub fn can_jump(&self) -> bool {
         self.on_ground && !self.is_jumping
     }
+
+    pub fn can_wall_jump(&self, props: &KaProperties) -> bool {
+        props.wall_jump && !self.on_ground && self.wall_normal != 0.0
+    }
+
+    pub fn can_air_jump(&self, props: &KaProperties) -> bool {
+        !self.on_ground && self.air_jumps_used < props.air_jumps
+    }
 }
 
+/// Kinematic actors with this component stay where they are, e.g. while the level they are in
+/// is being spawned and has no colliders yet
+#[derive(Component, Debug, Default)]
+pub struct KaFrozen;
+
 #[derive(Reflect, Default, Debug, PartialEq)]
 pub enum KaType {
     #[default]
@@ -168,7 +218,7 @@ pub fn kinematic_movement(
             &GlobalTransform,
             Option<&CollisionGroups>,
         ),
-        With<KinematicActor>,
+        (With<KinematicActor>, Without<KaFrozen>),
     >,
     time: Res<Time>,
     mut rapier_context: ResMut<RapierContext>,
@@ -219,22 +269,42 @@ pub fn kinematic_movement(
         let current = state.velocity;
         let wanted = target_velocity.reject_from_normalized(GRAVITY_DIR);
         let grav = state.velocity.project_onto_normalized(GRAVITY_DIR);
-        let mut velocity = move_towards_vec2(current, wanted + grav, velocity_change_speed * dt);
+        let mut velocity = if state.is_dashing {
+            current
+        } else {
+            move_towards_vec2(current, wanted + grav, velocity_change_speed * dt)
+        };
         // apply gravity
-        if !state.on_ground {
+        if !state.on_ground && !state.is_dashing {
             velocity += GRAVITY_DIR * GRAVITY_COEFFICIENT * dt;
         }
 
-        if input.jump.just_pressed() && state.can_jump() {
+        if input.jump.just_pressed() {
             // Calculate required jump velocity to reach given height
             let jump_velocity =
                 velocity_required_for_jump(props.jump_height * UNITS_PER_TILE, GRAVITY_COEFFICIENT);
             if let Some(jump_velocity) = jump_velocity {
-                velocity = Vec2 {
-                    y: jump_velocity,
-                    ..velocity
-                };
-                state.is_jumping = true;
+                if state.can_jump() {
+                    velocity = Vec2 {
+                        y: jump_velocity,
+                        ..velocity
+                    };
+                    state.is_jumping = true;
+                } else if state.can_wall_jump(&props) {
+                    // Kick off from the wall
+                    velocity = Vec2 {
+                        x: state.wall_normal * props.speed,
+                        y: jump_velocity,
+                    };
+                    state.is_jumping = true;
+                } else if state.can_air_jump(&props) {
+                    velocity = Vec2 {
+                        y: jump_velocity,
+                        ..velocity
+                    };
+                    state.is_jumping = true;
+                    state.air_jumps_used += 1;
+                }
             }
         }
 
@@ -372,6 +442,30 @@ pub fn kinematic_movement(
             None => false,
         };
 
+        // Look for a wall to jump off from
+        state.wall_normal = 0.0;
+        if props.wall_jump && !state.on_ground {
+            let (_scale, rotation, translation) = global_transform.to_scale_rotation_translation();
+            for direction in [Vec2::NEG_X, Vec2::X] {
+                if let Some((_, toi)) = rapier_context.cast_shape(
+                    translation.truncate(),
+                    rotation.to_euler(EulerRot::ZYX).0,
+                    direction,
+                    shape,
+                    1.0,
+                    move_filter,
+                ) {
+                    if toi.normal1.dot(-direction) > 0.7 {
+                        state.wall_normal = -direction.x;
+                    }
+                }
+            }
+        }
+
+        if state.on_ground {
+            state.air_jumps_used = 0;
+        }
+
         // Reset any possible jump snapping and stuff after the peak of jump
         if state.last_translation.dot(GRAVITY_DIR) >= 0.0 {
             state.is_jumping = false;
@@ -43,4 +43,7 @@ impl KaInputButton {
 pub struct KaInput {
     pub movement: Vec2,
     pub jump: KaInputButton,
+    pub dash: KaInputButton,
+    /// Read signs, talk to characters, etc.
+    pub interact: KaInputButton,
 }
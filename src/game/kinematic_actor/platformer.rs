@@ -7,18 +7,18 @@ use super::*;
 #[reflect(Component)]
 pub struct Platformer {
     is_short_hopping: bool,
+    /// Is dashing unlocked?
+    pub can_dash: bool,
+    /// Has the dash been used since last touching the ground?
+    dash_used: bool,
+    /// Time left in the current dash, in seconds
+    dash_timer: f32,
+    /// Horizontal direction the actor last moved towards
+    facing: f32,
 }
 
-// Short hop gravity is added after normal gravity,
-// so treat a multiplier of 0 as normal gravity and 1 as double
-
-/// Gravity multiplier added at the start of jump (linearly interpolated)
-const SHORT_HOP_GRAVITY_MULT_START: f32 = 3.0;
-/// Gravity multiplier added at the end of jump (linearly interpolated)
-const SHORT_HOP_GRAVITY_MULT_STOP: f32 = 0.5;
-
 pub fn platformer_system(
-    mut query: Query<(&mut KaState, &KaInput, &KaProperties, &mut Platformer)>,
+    mut query: Query<(&mut KaState, &KaInput, &KaProperties, &mut Platformer), Without<KaFrozen>>,
     time: Res<Time>,
 ) {
     let dt = time.delta_seconds();
@@ -33,8 +33,8 @@ pub fn platformer_system(
 
         if platformer.is_short_hopping {
             let gravity_mult = lerp(
-                SHORT_HOP_GRAVITY_MULT_STOP,
-                SHORT_HOP_GRAVITY_MULT_START,
+                props.short_hop_gravity_mult_stop,
+                props.short_hop_gravity_mult_start,
                 clamp(
                     state.velocity.project_onto(GRAVITY_DIR).length() / props.jump_velocity(),
                     0.0,
@@ -43,5 +43,33 @@ pub fn platformer_system(
             );
             state.velocity += GRAVITY_DIR * GRAVITY_COEFFICIENT * gravity_mult * dt;
         }
+
+        if input.movement.x != 0.0 {
+            platformer.facing = input.movement.x.signum();
+        }
+        if state.on_ground {
+            platformer.dash_used = false;
+        }
+
+        if platformer.can_dash
+            && !platformer.dash_used
+            && platformer.dash_timer <= 0.0
+            && input.dash.just_pressed()
+        {
+            platformer.dash_used = true;
+            platformer.dash_timer = props.dash_duration;
+        }
+
+        // Hold a fixed horizontal velocity for the duration of the dash, ignoring gravity
+        state.is_dashing = platformer.dash_timer > 0.0;
+        if state.is_dashing {
+            platformer.dash_timer -= dt;
+            let direction = if platformer.facing == 0.0 {
+                1.0
+            } else {
+                platformer.facing
+            };
+            state.velocity = Vec2::X * direction * props.speed * props.dash_speed_mult;
+        }
     }
 }
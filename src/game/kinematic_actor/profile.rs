@@ -0,0 +1,164 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::game::loading::RequiredAssets;
+
+/// Asset with the movement profiles used by [`KaProfile`]
+pub const MOVEMENT_PROFILES_PATH: &str = "tuning/actors.movement.ron";
+
+/// Tunable part of [`KaProperties`]. Abilities control the rest.
+///
+/// Missing fields use the values of [`KaProperties::default`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct MovementProfile {
+    pub speed: f32,
+    pub acceleration: f32,
+    pub friction: f32,
+    pub air_speed_mod: f32,
+    pub air_acceleration_mod: f32,
+    pub air_friction_mod: f32,
+    pub jump_height: f32,
+    pub short_hop_gravity_mult_start: f32,
+    pub short_hop_gravity_mult_stop: f32,
+    pub dash_speed_mult: f32,
+    pub dash_duration: f32,
+}
+
+impl MovementProfile {
+    pub fn apply(&self, props: &mut KaProperties) {
+        props.speed = self.speed;
+        props.acceleration = self.acceleration;
+        props.friction = self.friction;
+        props.air_speed_mod = self.air_speed_mod;
+        props.air_acceleration_mod = self.air_acceleration_mod;
+        props.air_friction_mod = self.air_friction_mod;
+        props.jump_height = self.jump_height;
+        props.short_hop_gravity_mult_start = self.short_hop_gravity_mult_start;
+        props.short_hop_gravity_mult_stop = self.short_hop_gravity_mult_stop;
+        props.dash_speed_mult = self.dash_speed_mult;
+        props.dash_duration = self.dash_duration;
+    }
+}
+
+impl From<&KaProperties> for MovementProfile {
+    fn from(props: &KaProperties) -> Self {
+        Self {
+            speed: props.speed,
+            acceleration: props.acceleration,
+            friction: props.friction,
+            air_speed_mod: props.air_speed_mod,
+            air_acceleration_mod: props.air_acceleration_mod,
+            air_friction_mod: props.air_friction_mod,
+            jump_height: props.jump_height,
+            short_hop_gravity_mult_start: props.short_hop_gravity_mult_start,
+            short_hop_gravity_mult_stop: props.short_hop_gravity_mult_stop,
+            dash_speed_mult: props.dash_speed_mult,
+            dash_duration: props.dash_duration,
+        }
+    }
+}
+
+impl Default for MovementProfile {
+    fn default() -> Self {
+        Self::from(&KaProperties::default())
+    }
+}
+
+/// Movement profiles by name, loaded from `.movement.ron` files
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "0b8f5d52-6c1e-4f0a-b6e3-7a4d2c9e1f38"]
+#[serde(transparent)]
+pub struct MovementProfiles {
+    pub profiles: HashMap<String, MovementProfile>,
+}
+
+#[derive(Default)]
+pub struct MovementProfilesLoader;
+
+impl AssetLoader for MovementProfilesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let profiles: MovementProfiles = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(profiles));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["movement.ron"]
+    }
+}
+
+/// Handle keeping the [`MovementProfiles`] at [`MOVEMENT_PROFILES_PATH`] loaded
+#[derive(Resource, Debug)]
+pub struct MovementProfilesHandle(pub Handle<MovementProfiles>);
+
+/// Sets the [`KaProperties`] of the actor from the [`MovementProfile`] of the given name
+#[derive(Reflect, Component, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct KaProfile {
+    pub name: String,
+}
+
+impl KaProfile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+pub fn load_movement_profiles(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut required: ResMut<RequiredAssets>,
+) {
+    let handle = assets.load(MOVEMENT_PROFILES_PATH);
+    required.add(&handle);
+    commands.insert_resource(MovementProfilesHandle(handle));
+}
+
+/// Applies the profiles to new actors, and to every actor when the profiles are (re)loaded
+pub fn apply_movement_profiles(
+    mut profile_events: EventReader<AssetEvent<MovementProfiles>>,
+    profiles: Res<Assets<MovementProfiles>>,
+    profiles_handle: Option<Res<MovementProfilesHandle>>,
+    mut query: Query<(Entity, &KaProfile, &mut KaProperties)>,
+    changed_query: Query<(), Changed<KaProfile>>,
+) {
+    let profiles_handle = match profiles_handle {
+        Some(profiles_handle) => profiles_handle,
+        None => return,
+    };
+    let reloaded = profile_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == profiles_handle.0
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    let profiles = match profiles.get(&profiles_handle.0) {
+        Some(profiles) => profiles,
+        None => return,
+    };
+
+    for (entity, profile, mut props) in query.iter_mut() {
+        if !reloaded && !changed_query.contains(entity) {
+            continue;
+        }
+        match profiles.profiles.get(&profile.name) {
+            Some(movement) => movement.apply(&mut props),
+            None => warn!("Unknown movement profile: {}", profile.name),
+        }
+    }
+}
@@ -1,10 +1,18 @@
 use bevy::ecs::prelude::*;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use bevy_ecs_ldtk::ldtk::EnumValueDefinition;
 use bevy_ecs_ldtk::{prelude::*, LdtkSystemLabel};
-use bevy_rapier2d::prelude::*;
-use std::collections::HashSet;
+
+mod grid;
+mod hazard;
+mod plugin;
+mod wall;
+
+pub use grid::*;
+pub use hazard::*;
+pub use plugin::*;
+pub use wall::*;
 
 pub struct LdtkHelperPlugin;
 
@@ -12,8 +20,14 @@ impl Plugin for LdtkHelperPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<EntityInstanceAdded>()
             .register_ldtk_int_cell::<WallBundle>(1)
+            .register_ldtk_int_cell::<SpikesBundle>(SPIKES_VALUE)
             .insert_resource(WordlyInstances::default())
+            .init_resource::<WorldState>()
             .insert_resource(LdtkEnum::default())
+            .init_resource::<WallColliderSettings>()
+            .init_resource::<WallLayers>()
+            .init_resource::<HazardLayers>()
+            .init_resource::<LevelGrids>()
             .add_system_to_stage(CoreStage::PreUpdate, entity_instance_events)
             .add_system_to_stage(CoreStage::PostUpdate, entity_namer)
             .add_system_to_stage(
@@ -24,6 +38,18 @@ impl Plugin for LdtkHelperPlugin {
                 CoreStage::PreUpdate,
                 wall_setup.after(LdtkSystemLabel::LevelSpawning),
             )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                hazard_setup.after(LdtkSystemLabel::LevelSpawning),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                worldly_reload_handler.after(LdtkSystemLabel::ProcessAssets),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, level_reload_handler)
+            .add_system_to_stage(CoreStage::PostUpdate, wall_removal_tracker)
+            .add_system_to_stage(CoreStage::PostUpdate, hazard_removal_tracker)
+            .add_system_to_stage(CoreStage::PostUpdate, level_grid_update)
             .add_system_to_stage(CoreStage::PostUpdate, unique_handler);
     }
 }
@@ -38,22 +64,53 @@ pub struct WordlyInstances {
     pub def_uid_map: HashMap<i32, Entity>,
 }
 
-#[derive(Bundle, LdtkIntCell, Default, Clone, Debug)]
-pub struct WallBundle {
-    wall: Wall,
+/// Persistent changes to the LDtk entities that outlive the levels they are in.
+///
+/// Levels are despawned and respawned as the player moves around, which would bring back anything
+/// that was collected or destroyed, so those are remembered by their entity iid.
+/// Story progress is kept as named flags, set by dialogue and checked by gates and pickups.
+#[derive(Resource, Default, Debug)]
+pub struct WorldState {
+    pub removed_iids: HashSet<String>,
+    pub flags: HashSet<String>,
 }
 
-#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
-#[reflect(Component)]
-pub struct Wall;
+impl WorldState {
+    /// Remember the entity as gone for good, so it's not set up again when its level respawns
+    pub fn remove(&mut self, iid: &str) {
+        self.removed_iids.insert(iid.to_string());
+    }
+
+    pub fn is_removed(&self, iid: &str) -> bool {
+        self.removed_iids.contains(iid)
+    }
+
+    pub fn set_flag(&mut self, flag: &str, value: bool) {
+        if value {
+            self.flags.insert(flag.to_string());
+        } else {
+            self.flags.remove(flag);
+        }
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+}
 
 fn entity_instance_events(
     query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
     worldly_instances: Res<WordlyInstances>,
+    world_state: Res<WorldState>,
     mut events: EventWriter<EntityInstanceAdded>,
     mut commands: Commands,
 ) {
     for (entity, instance) in query.iter() {
+        if world_state.is_removed(&instance.iid) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
         // Spawn the entity if it's not in the unique instances list (or if the old one is deleted)
         // TODO: Detect deleted entities safely: https://github.com/bevyengine/bevy/issues/3845
         if worldly_instances
@@ -109,8 +166,11 @@ fn map_enum_defs(
 ) {
     for event in ldtk_events.iter() {
         match event {
-            AssetEvent::Created { handle } => {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
                 if let Some(ldtk_asset) = ldtk_assets.get(handle) {
+                    // Start from scratch so removed or renamed values don't linger after a reload
+                    ldtk_enum.items.clear();
+
                     if let Some(enum_def) = ldtk_asset
                         .project
                         .defs
@@ -134,7 +194,11 @@ fn map_enum_defs(
                                     Some(Vec2::splat(tileset.spacing as f32)),
                                     Some(Vec2::splat(0.0)),
                                 );
-                                ldtk_enum.item_atlas = atlases.add(atlas);
+                                let old_atlas = std::mem::replace(
+                                    &mut ldtk_enum.item_atlas,
+                                    atlases.add(atlas),
+                                );
+                                atlases.remove(old_atlas);
                             }
 
                             for enum_value in enum_def.values.iter() {
@@ -151,171 +215,74 @@ fn map_enum_defs(
     }
 }
 
-/// Wall collider system from bevy_ecs_ldtk example.
-///
-/// Spawns colliders for the walls of a level
-///
-/// You could just insert a ColliderBundle in to the WallBundle,
-/// but this spawns a different collider for EVERY wall tile.
-/// This approach leads to bad performance.
+/// Keeps [`Worldly`] entities alive when the LDtk project is hot-reloaded.
 ///
-/// Instead, by flagging the wall tiles and spawning the collisions later,
-/// we can minimize the amount of colliding entities.
-///
-/// The algorithm used here is a nice compromise between simplicity, speed,
-/// and a small number of rectangle colliders.
-/// In basic terms, it will:
-/// 1. consider where the walls are
-/// 2. combine wall tiles into flat "plates" in each individual row
-/// 3. combine the plates into rectangles across multiple rows wherever possible
-/// 4. spawn colliders for each rectangle
-pub fn wall_setup(
+/// On modification bevy_ecs_ldtk marks the world with [`Respawn`], which despawns every child of
+/// the world, including worldly entities like the player. To avoid that, they are detached from
+/// the world for the duration of the respawn and adopted back once the levels are spawned again.
+/// The entities in [`WordlyInstances`] stay the same, so nothing gets spawned twice.
+fn worldly_reload_handler(
     mut commands: Commands,
-    wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
-    parent_query: Query<&Parent, Without<Wall>>,
-    level_query: Query<(Entity, &Handle<LdtkLevel>)>,
-    levels: Res<Assets<LdtkLevel>>,
+    mut ldtk_events: EventReader<AssetEvent<LdtkAsset>>,
+    world_query: Query<(Entity, &Handle<LdtkAsset>, Option<&Respawn>)>,
+    worldly_query: Query<(Entity, &Parent), With<Worldly>>,
+    mut detached: Local<Vec<(Entity, Entity)>>,
 ) {
-    /// Represents a wide wall that is 1 tile tall
-    /// Used to spawn wall collisions
-    #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
-    struct Plate {
-        left: i32,
-        right: i32,
+    let mut reloaded = false;
+    for event in ldtk_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            reloaded = true;
+            for (world_entity, world_handle, _) in world_query.iter() {
+                if world_handle != handle {
+                    continue;
+                }
+                for (entity, parent) in worldly_query.iter() {
+                    if parent.get() == world_entity {
+                        commands.entity(entity).remove_parent();
+                        detached.push((entity, world_entity));
+                    }
+                }
+            }
+        }
     }
 
-    /// A simple rectangle type representing a wall of any size
-    struct Rect {
-        left: i32,
-        right: i32,
-        top: i32,
-        bottom: i32,
+    // The respawn is only finished once the world doesn't have the Respawn component anymore.
+    // Respawn is inserted later in this stage, so don't check on the same frame as the event.
+    if reloaded {
+        return;
     }
-
-    // Consider where the walls are
-    // storing them as GridCoords in a HashSet for quick, easy lookup
-    //
-    // The key of this map will be the entity of the level the wall belongs to.
-    // This has two consequences in the resulting collision entities:
-    // 1. it forces the walls to be split along level boundaries
-    // 2. it lets us easily add the collision entities as children of the appropriate level entity
-    let mut level_to_wall_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
-
-    wall_query.for_each(|(&grid_coords, parent)| {
-        // An intgrid tile's direct parent will be a layer entity, not the level entity
-        // To get the level entity, you need the tile's grandparent.
-        // This is where parent_query comes in.
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_wall_locations
-                .entry(grandparent.get())
-                .or_default()
-                .insert(grid_coords);
+    detached.retain(|(entity, world_entity)| {
+        match world_query.get(*world_entity) {
+            Ok((_, _, Some(_respawn))) => true,
+            Ok((_, _, None)) => {
+                if commands.get_entity(*entity).is_some() {
+                    commands.entity(*world_entity).add_child(*entity);
+                }
+                false
+            }
+            // The world itself is gone, leave the entity be
+            Err(_) => false,
         }
     });
+}
 
-    if !wall_query.is_empty() {
-        level_query.for_each(|(level_entity, level_handle)| {
-            if let Some(level_walls) = level_to_wall_locations.get(&level_entity) {
-                let level = levels
-                    .get(level_handle)
-                    .expect("Level should be loaded by this point");
-
-                let LayerInstance {
-                    c_wid: width,
-                    c_hei: height,
-                    grid_size,
-                    ..
-                } = level
-                    .level
-                    .layer_instances
-                    .clone()
-                    .expect("Level asset should have layers")[0];
-
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    // + 1 to the width so the algorithm "terminates" plates that touch the right edge
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
-                    }
-
-                    plate_stack.push(row_plates);
-                }
-
-                // combine "plates" into rectangles across multiple rows
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
-
-                // an extra empty row so the algorithm "finishes" the rects that touch the top edge
-                plate_stack.push(Vec::new());
-
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            // remove the finished rect so that the same plate in the future starts a new rect
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
-                        }
-                    }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
-                    }
-                    prev_row = current_row;
+/// Respawns levels whose [`LdtkLevel`] asset was modified.
+///
+/// This is only needed for projects with external level files, since changes to the project file
+/// itself respawn the whole world. Respawning the level also regenerates its wall colliders through
+/// [`wall_setup`], as the colliders are children of the level.
+fn level_reload_handler(
+    mut commands: Commands,
+    mut level_events: EventReader<AssetEvent<LdtkLevel>>,
+    level_query: Query<(Entity, &Handle<LdtkLevel>)>,
+) {
+    for event in level_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            for (level_entity, level_handle) in level_query.iter() {
+                if level_handle == handle {
+                    commands.entity(level_entity).insert(Respawn);
                 }
-
-                commands.entity(level_entity).with_children(|level| {
-                    // Spawn colliders for every rectangle..
-                    // Making the collider a child of the level serves two purposes:
-                    // 1. Adjusts the transforms to be relative to the level for free
-                    // 2. the colliders will be despawned automatically when levels unload
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
-                            ))
-                            .insert(GlobalTransform::default());
-                    }
-                });
             }
-        });
+        }
     }
 }
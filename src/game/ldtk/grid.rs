@@ -0,0 +1,703 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_ecs_ldtk::ldtk::Type;
+use bevy_ecs_ldtk::prelude::*;
+
+use super::Wall;
+use crate::{game::tile::TILE_SIZE, util::Vector2I};
+
+/// Size of the squares of the world [`LevelGrids`] sorts the levels into, in world units
+const BUCKET_SIZE: f32 = 128.0;
+
+/// IntGrid data of every spawned level, keyed by level iid.
+///
+/// Lets gameplay code ask questions like "is this cell solid" without going through the tile
+/// entities or the physics colliders.
+#[derive(Resource, Default)]
+pub struct LevelGrids {
+    levels: HashMap<String, LevelGrid>,
+    /// IntGrid tile entity to its level iid, layer and position, used to find the cell of removed
+    /// walls
+    tiles: HashMap<Entity, GridTile>,
+    /// Iids of the levels overlapping each bucket, so finding the level at a position doesn't have
+    /// to go through all of them
+    buckets: HashMap<IVec2, Vec<String>>,
+}
+
+impl LevelGrids {
+    pub fn get(&self, level_iid: &str) -> Option<&LevelGrid> {
+        self.levels.get(level_iid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LevelGrid> {
+        self.levels.values()
+    }
+
+    /// Adds a grid that isn't backed by spawned tiles, replacing the one of the same level
+    pub fn insert(&mut self, grid: LevelGrid) {
+        self.levels.insert(grid.iid.clone(), grid);
+        self.rebuild_buckets();
+    }
+
+    fn bucket(position: Vec2) -> IVec2 {
+        (position / BUCKET_SIZE).floor().as_ivec2()
+    }
+
+    fn rebuild_buckets(&mut self) {
+        self.buckets.clear();
+        for grid in self.levels.values() {
+            let min = Self::bucket(grid.origin);
+            let max = Self::bucket(grid.origin + Vec2::from(grid.size) * TILE_SIZE);
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.buckets
+                        .entry(IVec2::new(x, y))
+                        .or_default()
+                        .push(grid.iid.clone());
+                }
+            }
+        }
+    }
+
+    /// Level grid containing the given world position
+    pub fn level_at(&self, position: Vec2) -> Option<&LevelGrid> {
+        self.buckets
+            .get(&Self::bucket(position))?
+            .iter()
+            .filter_map(|iid| self.levels.get(iid))
+            .find(|grid| grid.contains(grid.world_to_grid(position)))
+    }
+
+    pub fn is_solid_at(&self, position: Vec2) -> bool {
+        self.level_at(position)
+            .map_or(false, |grid| grid.is_solid(grid.world_to_grid(position)))
+    }
+}
+
+impl From<GridCoords> for Vector2I {
+    fn from(coords: GridCoords) -> Self {
+        Self {
+            x: coords.x,
+            y: coords.y,
+        }
+    }
+}
+
+impl From<Vector2I> for GridCoords {
+    fn from(vec: Vector2I) -> Self {
+        Self { x: vec.x, y: vec.y }
+    }
+}
+
+struct GridTile {
+    iid: String,
+    layer: String,
+    cell: Vector2I,
+}
+
+/// IntGrid values and solidity of a single level.
+///
+/// Every IntGrid layer of the level is kept separately, and the lookups merge them: a cell is solid
+/// if it's a [`Wall`] in any layer, and its value is the one of the topmost layer which has one.
+///
+/// Cells are in the same coordinates as [`GridCoords`]: (0, 0) is the bottom left cell and y goes up.
+pub struct LevelGrid {
+    pub iid: String,
+    pub identifier: String,
+    pub level_entity: Entity,
+    /// World position of the bottom left corner of the level
+    pub origin: Vec2,
+    pub size: Vector2I,
+    /// From the top, in the same order as in LDtk
+    layers: Vec<GridLayer>,
+    revision: u32,
+}
+
+struct GridLayer {
+    identifier: String,
+    values: Vec<i32>,
+    solid: Vec<bool>,
+}
+
+/// Result of [`LevelGrid::raycast`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridRayHit {
+    pub cell: Vector2I,
+    /// Normal of the cell side that was hit. Zero if the ray started inside a solid cell.
+    pub normal: Vector2I,
+    /// Distance from the ray origin in world units
+    pub distance: f32,
+    pub point: Vec2,
+}
+
+impl LevelGrid {
+    pub fn new(
+        iid: String,
+        identifier: String,
+        level_entity: Entity,
+        origin: Vec2,
+        size: Vector2I,
+    ) -> Self {
+        Self {
+            iid,
+            identifier,
+            level_entity,
+            origin,
+            size,
+            layers: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    /// Adds an empty layer below the existing ones, unless there already is one with the identifier
+    pub fn add_layer(&mut self, identifier: &str) {
+        if self.layer(identifier).is_none() {
+            let len = (self.size.x * self.size.y).max(0) as usize;
+            self.layers.push(GridLayer {
+                identifier: identifier.to_string(),
+                values: vec![0; len],
+                solid: vec![false; len],
+            });
+        }
+    }
+
+    fn layer(&self, identifier: &str) -> Option<&GridLayer> {
+        self.layers
+            .iter()
+            .find(|layer| layer.identifier == identifier)
+    }
+
+    fn layer_mut(&mut self, identifier: &str) -> &mut GridLayer {
+        self.add_layer(identifier);
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.identifier == identifier)
+            .unwrap()
+    }
+
+    fn index(&self, cell: Vector2I) -> Option<usize> {
+        if self.contains(cell) {
+            Some((cell.y * self.size.x + cell.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, cell: Vector2I) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.size.x && cell.y < self.size.y
+    }
+
+    /// IntGrid value of the cell in the topmost layer that has one, 0 being empty.
+    /// None if the cell is outside of the level.
+    pub fn value(&self, cell: Vector2I) -> Option<i32> {
+        self.index(cell).map(|i| {
+            self.layers
+                .iter()
+                .map(|layer| layer.values[i])
+                .find(|value| *value != 0)
+                .unwrap_or(0)
+        })
+    }
+
+    /// IntGrid value of the cell in a single layer. None if the cell is outside of the level, or
+    /// the level doesn't have the layer.
+    pub fn layer_value(&self, layer: &str, cell: Vector2I) -> Option<i32> {
+        let i = self.index(cell)?;
+        self.layer(layer).map(|layer| layer.values[i])
+    }
+
+    /// Is the cell a [`Wall`] in any layer? Cells outside of the level are never solid.
+    pub fn is_solid(&self, cell: Vector2I) -> bool {
+        self.index(cell)
+            .map_or(false, |i| self.layers.iter().any(|layer| layer.solid[i]))
+    }
+
+    /// Incremented every time a cell changes, so derived data knows when to update
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub(crate) fn set(&mut self, layer: &str, cell: Vector2I, value: i32, solid: bool) {
+        if let Some(i) = self.index(cell) {
+            let layer = self.layer_mut(layer);
+            layer.values[i] = value;
+            layer.solid[i] = solid;
+            self.revision += 1;
+        }
+    }
+
+    fn set_solid(&mut self, layer: &str, cell: Vector2I, solid: bool) {
+        if let Some(i) = self.index(cell) {
+            self.layer_mut(layer).solid[i] = solid;
+            self.revision += 1;
+        }
+    }
+
+    /// Cell containing the world position
+    pub fn world_to_grid(&self, position: Vec2) -> Vector2I {
+        let cell = ((position - self.origin) / TILE_SIZE).floor();
+        Vector2I::new(cell.x as i32, cell.y as i32)
+    }
+
+    /// World position of the center of the cell
+    pub fn grid_to_world(&self, cell: Vector2I) -> Vec2 {
+        self.origin + (Vec2::from(cell) + Vec2::splat(0.5)) * TILE_SIZE
+    }
+
+    /// Casts a ray through the grid, returning the first solid cell it hits.
+    ///
+    /// Uses a DDA traversal, so every cell along the ray is visited exactly once.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<GridRayHit> {
+        let direction = direction.try_normalize()?;
+        let start = (origin - self.origin) / TILE_SIZE;
+        let max_t = max_distance / TILE_SIZE;
+
+        let mut cell = self.world_to_grid(origin);
+        if self.is_solid(cell) {
+            return Some(GridRayHit {
+                cell,
+                normal: Vector2I::ZERO,
+                distance: 0.0,
+                point: origin,
+            });
+        }
+
+        // signum() is never 0, which would step along an axis the ray doesn't move on
+        let axis_step = |d: f32| {
+            if d > 0.0 {
+                1
+            } else if d < 0.0 {
+                -1
+            } else {
+                0
+            }
+        };
+        let step = Vector2I::new(axis_step(direction.x), axis_step(direction.y));
+        // Distance along the ray (in cells) between two vertical or horizontal cell sides
+        let t_delta = Vec2::new(
+            if step.x == 0 {
+                f32::INFINITY
+            } else {
+                1.0 / direction.x.abs()
+            },
+            if step.y == 0 {
+                f32::INFINITY
+            } else {
+                1.0 / direction.y.abs()
+            },
+        );
+        // Distance along the ray (in cells) to the next vertical or horizontal cell side
+        let mut t_max = Vec2::new(
+            match step.x {
+                1 => (cell.x as f32 + 1.0 - start.x) * t_delta.x,
+                -1 => (start.x - cell.x as f32) * t_delta.x,
+                _ => f32::INFINITY,
+            },
+            match step.y {
+                1 => (cell.y as f32 + 1.0 - start.y) * t_delta.y,
+                -1 => (start.y - cell.y as f32) * t_delta.y,
+                _ => f32::INFINITY,
+            },
+        );
+
+        loop {
+            let (t, normal) = if t_max.x < t_max.y {
+                cell.x += step.x;
+                t_max.x += t_delta.x;
+                (t_max.x - t_delta.x, Vector2I::new(-step.x, 0))
+            } else {
+                cell.y += step.y;
+                t_max.y += t_delta.y;
+                (t_max.y - t_delta.y, Vector2I::new(0, -step.y))
+            };
+
+            if t > max_t {
+                return None;
+            }
+
+            // Nothing to hit once the ray has left the level for good
+            let leaving_x = (cell.x < 0 && step.x <= 0) || (cell.x >= self.size.x && step.x >= 0);
+            let leaving_y = (cell.y < 0 && step.y <= 0) || (cell.y >= self.size.y && step.y >= 0);
+            if leaving_x || leaving_y {
+                return None;
+            }
+
+            if self.is_solid(cell) {
+                let distance = t * TILE_SIZE;
+                return Some(GridRayHit {
+                    cell,
+                    normal,
+                    distance,
+                    point: origin + direction * distance,
+                });
+            }
+        }
+    }
+
+    /// Solid cells inside the inclusive region
+    pub fn solid_cells_in(
+        &self,
+        min: Vector2I,
+        max: Vector2I,
+    ) -> impl Iterator<Item = Vector2I> + '_ {
+        self.cells_in(min, max).filter(|cell| self.is_solid(*cell))
+    }
+
+    /// Are all the cells inside the inclusive region free of walls?
+    pub fn is_region_clear(&self, min: Vector2I, max: Vector2I) -> bool {
+        self.solid_cells_in(min, max).next().is_none()
+    }
+
+    /// Cells inside the inclusive region, clamped to the level
+    pub fn cells_in(&self, min: Vector2I, max: Vector2I) -> impl Iterator<Item = Vector2I> {
+        let min = min.max(&Vector2I::ZERO);
+        let max = max.min(&(self.size - Vector2I::ONE));
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Vector2I::new(x, y)))
+    }
+
+    /// All cells with the given IntGrid value
+    pub fn cells_with_value(&self, value: i32) -> impl Iterator<Item = Vector2I> + '_ {
+        self.cells_in(Vector2I::ZERO, self.size - Vector2I::ONE)
+            .filter(move |cell| self.value(*cell) == Some(value))
+    }
+}
+
+/// IntGrid tiles spawned this frame
+type AddedCellQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GridCoords,
+        &'static IntGridCell,
+        &'static Parent,
+        Option<&'static Wall>,
+    ),
+    Added<IntGridCell>,
+>;
+
+/// Keeps [`LevelGrids`] in sync with the spawned IntGrid tiles and their [`Wall`] components.
+///
+/// The grid of a level is built from scratch every time the level spawns, including when it's
+/// respawned in place after a hot reload, so no cells of the old tiles are left behind.
+///
+/// Runs late in the frame so walls removed during the frame are still visible.
+pub fn level_grid_update(
+    cell_query: AddedCellQuery,
+    (wall_query, removed_walls): (Query<Entity, Added<Wall>>, RemovedComponents<Wall>),
+    mut level_events: EventReader<LevelEvent>,
+    layer_query: Query<(&LayerMetadata, &Parent)>,
+    level_query: Query<(&Handle<LdtkLevel>, &Transform)>,
+    levels: Res<Assets<LdtkLevel>>,
+    mut level_grids: ResMut<LevelGrids>,
+) {
+    let level_grids = &mut *level_grids;
+    let mut levels_changed = false;
+
+    // The tiles of a (re)spawned level are all added this frame
+    for event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = event {
+            levels_changed |= level_grids.levels.remove(iid).is_some();
+            level_grids.tiles.retain(|_, tile| tile.iid != *iid);
+        }
+    }
+
+    for (entity, grid_coords, cell, parent, wall) in cell_query.iter() {
+        // Like walls, an IntGrid tile's parent is the layer and the grandparent is the level
+        if let Some((layer, level_entity, level_transform, level)) = layer_query
+            .get(parent.get())
+            .ok()
+            .and_then(|(layer, level_entity)| {
+                let (level_handle, level_transform) = level_query.get(level_entity.get()).ok()?;
+                Some((
+                    layer,
+                    level_entity.get(),
+                    level_transform,
+                    levels.get(level_handle)?,
+                ))
+            })
+        {
+            let iid = &level.level.iid;
+            let new_grid = || {
+                let mut grid = LevelGrid::new(
+                    iid.clone(),
+                    level.level.identifier.clone(),
+                    level_entity,
+                    level_transform.translation.truncate(),
+                    Vector2I::new(layer.c_wid, layer.c_hei),
+                );
+                for layer in level.level.layer_instances.iter().flatten() {
+                    if layer.layer_instance_type == Type::IntGrid {
+                        grid.add_layer(&layer.identifier);
+                    }
+                }
+                grid
+            };
+            levels_changed |= !level_grids.levels.contains_key(iid);
+            let grid = level_grids
+                .levels
+                .entry(iid.clone())
+                .or_insert_with(new_grid);
+            // The level may have been despawned and spawned again since the grid was built
+            if grid.level_entity != level_entity {
+                *grid = new_grid();
+                levels_changed = true;
+            }
+
+            let cell_position = Vector2I::from(*grid_coords);
+            grid.set(&layer.identifier, cell_position, cell.value, wall.is_some());
+            level_grids.tiles.insert(
+                entity,
+                GridTile {
+                    iid: iid.clone(),
+                    layer: layer.identifier.clone(),
+                    cell: cell_position,
+                },
+            );
+        }
+    }
+
+    // Walls added or removed at runtime
+    for (entity, solid) in wall_query
+        .iter()
+        .map(|entity| (entity, true))
+        .chain(removed_walls.iter().map(|entity| (entity, false)))
+    {
+        if let Some(tile) = level_grids.tiles.get(&entity) {
+            if let Some(grid) = level_grids.levels.get_mut(&tile.iid) {
+                grid.set_solid(&tile.layer, tile.cell, solid);
+            }
+        }
+    }
+
+    // Forget despawned levels
+    if !level_grids.levels.is_empty() {
+        let level_count = level_grids.levels.len();
+        level_grids
+            .levels
+            .retain(|_, grid| level_query.contains(grid.level_entity));
+        levels_changed |= level_grids.levels.len() != level_count;
+        let level_iids = &level_grids.levels;
+        level_grids
+            .tiles
+            .retain(|_, tile| level_iids.contains_key(&tile.iid));
+    }
+
+    if levels_changed {
+        level_grids.rebuild_buckets();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 8x4 level at the origin, with a wall column at x = 0 and a wall row at y = 3
+    fn level() -> LevelGrid {
+        let mut grid = LevelGrid::new(
+            "iid".to_string(),
+            "LEVEL".to_string(),
+            Entity::from_raw(0),
+            Vec2::ZERO,
+            Vector2I::new(8, 4),
+        );
+        for y in 0..4 {
+            grid.set("GRID", Vector2I::new(0, y), 1, true);
+        }
+        for x in 0..8 {
+            grid.set("GRID", Vector2I::new(x, 3), 1, true);
+        }
+        grid
+    }
+
+    #[test]
+    fn layers_are_merged() {
+        let mut grid = level();
+        grid.add_layer("HAZARDS");
+        grid.set("HAZARDS", Vector2I::new(4, 0), 2, false);
+        grid.set("HAZARDS", Vector2I::new(0, 0), 2, false);
+
+        // Walls of the first layer aren't overwritten by the second one
+        assert!(grid.is_solid(Vector2I::new(0, 0)));
+        assert_eq!(grid.value(Vector2I::new(0, 0)), Some(1));
+        assert_eq!(grid.layer_value("HAZARDS", Vector2I::new(0, 0)), Some(2));
+        assert!(!grid.is_solid(Vector2I::new(4, 0)));
+        assert_eq!(grid.value(Vector2I::new(4, 0)), Some(2));
+        assert_eq!(grid.value(Vector2I::new(5, 0)), Some(0));
+        assert_eq!(grid.layer_value("MISSING", Vector2I::new(5, 0)), None);
+        assert_eq!(grid.cells_with_value(2).count(), 1);
+    }
+
+    /// Spawns the IntGrid tiles of the layers as children of the level
+    fn spawn_layers(world: &mut World, level: Entity, layers: &[(&str, &[(i32, i32, i32, bool)])]) {
+        for (identifier, cells) in layers {
+            let layer = world
+                .spawn(LayerMetadata {
+                    identifier: identifier.to_string(),
+                    c_wid: 8,
+                    c_hei: 4,
+                    ..default()
+                })
+                .id();
+            world.entity_mut(level).push_children(&[layer]);
+            for (x, y, value, wall) in cells.iter() {
+                let mut cell =
+                    world.spawn((GridCoords::new(*x, *y), IntGridCell { value: *value }));
+                if *wall {
+                    cell.insert(Wall);
+                }
+                let cell = cell.id();
+                world.entity_mut(layer).push_children(&[cell]);
+            }
+        }
+    }
+
+    #[test]
+    fn grids_follow_spawned_levels() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<LdtkLevel>()
+            .add_event::<LevelEvent>()
+            .init_resource::<LevelGrids>()
+            .add_system(level_grid_update);
+
+        let iid = "level".to_string();
+        let handle = app
+            .world
+            .resource_mut::<Assets<LdtkLevel>>()
+            .add(LdtkLevel {
+                level: bevy_ecs_ldtk::ldtk::Level {
+                    iid: iid.clone(),
+                    ..default()
+                },
+                background_image: None,
+            });
+        let level = app.world.spawn((handle, Transform::default())).id();
+        spawn_layers(
+            &mut app.world,
+            level,
+            &[
+                ("GRID", &[(0, 0, 1, true), (1, 0, 1, true)]),
+                ("HAZARDS", &[(2, 0, 2, false)]),
+            ],
+        );
+        app.update();
+
+        let grid = app.world.resource::<LevelGrids>().get(&iid).unwrap();
+        assert!(grid.is_solid(Vector2I::new(0, 0)));
+        assert!(grid.is_solid(Vector2I::new(1, 0)));
+        assert_eq!(grid.value(Vector2I::new(2, 0)), Some(2));
+
+        // Respawning the level in place replaces all of its tiles, but not the level entity
+        let layers: Vec<Entity> = app.world.get::<Children>(level).unwrap().to_vec();
+        for layer in layers {
+            despawn_with_children_recursive(&mut app.world, layer);
+        }
+        spawn_layers(
+            &mut app.world,
+            level,
+            &[("GRID", &[(1, 0, 1, true)]), ("HAZARDS", &[])],
+        );
+        app.world
+            .resource_mut::<Events<LevelEvent>>()
+            .send(LevelEvent::Spawned(iid.clone()));
+        app.update();
+
+        let grid = app.world.resource::<LevelGrids>().get(&iid).unwrap();
+        assert!(!grid.is_solid(Vector2I::new(0, 0)));
+        assert!(grid.is_solid(Vector2I::new(1, 0)));
+        assert_eq!(grid.value(Vector2I::new(2, 0)), Some(0));
+
+        despawn_with_children_recursive(&mut app.world, level);
+        app.update();
+        assert!(app.world.resource::<LevelGrids>().get(&iid).is_none());
+    }
+
+    #[test]
+    fn raycast_axis_aligned() {
+        let grid = level();
+        let origin = grid.grid_to_world(Vector2I::new(4, 1));
+
+        let hit = grid.raycast(origin, -Vec2::X, 100.0).unwrap();
+        assert_eq!(hit.cell, Vector2I::new(0, 1));
+        assert_eq!(hit.normal, Vector2I::new(1, 0));
+        assert_eq!(hit.distance, 3.5 * TILE_SIZE);
+        assert_eq!(hit.point, Vec2::new(TILE_SIZE, origin.y));
+
+        let hit = grid.raycast(origin, Vec2::Y, 100.0).unwrap();
+        assert_eq!(hit.cell, Vector2I::new(4, 3));
+        assert_eq!(hit.normal, Vector2I::new(0, -1));
+        assert_eq!(hit.distance, 1.5 * TILE_SIZE);
+
+        // Nothing to the right or below
+        assert_eq!(grid.raycast(origin, Vec2::X, 100.0), None);
+        assert_eq!(grid.raycast(origin, -Vec2::Y, 100.0), None);
+        // Out of range
+        assert_eq!(grid.raycast(origin, -Vec2::X, 3.0 * TILE_SIZE), None);
+    }
+
+    #[test]
+    fn raycast_from_cell_boundary() {
+        let grid = level();
+        // Exactly on the corner of four cells, where a zero direction component used to give NaN
+        let origin = Vec2::new(3.0, 2.0) * TILE_SIZE;
+
+        let hit = grid.raycast(origin, -Vec2::X, 100.0).unwrap();
+        assert_eq!(hit.cell, Vector2I::new(0, 2));
+        assert_eq!(hit.distance, 2.0 * TILE_SIZE);
+
+        let hit = grid.raycast(origin, Vec2::Y, 100.0).unwrap();
+        assert_eq!(hit.cell, Vector2I::new(3, 3));
+        assert_eq!(hit.distance, TILE_SIZE);
+
+        let hit = grid.raycast(origin, -Vec2::Y, 100.0);
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn raycast_diagonal() {
+        let grid = level();
+        let origin = grid.grid_to_world(Vector2I::new(2, 1));
+        let hit = grid.raycast(origin, Vec2::new(-1.0, 1.0), 100.0).unwrap();
+        // Passes exactly through cell corners, where the vertical step is taken first
+        assert_eq!(hit.cell, Vector2I::new(1, 3));
+        assert_eq!(hit.normal, Vector2I::new(0, -1));
+    }
+
+    #[test]
+    fn raycast_starting_in_wall() {
+        let grid = level();
+        let origin = grid.grid_to_world(Vector2I::new(0, 0));
+        let hit = grid.raycast(origin, Vec2::X, 100.0).unwrap();
+        assert_eq!(hit.normal, Vector2I::ZERO);
+        assert_eq!(hit.distance, 0.0);
+    }
+
+    #[test]
+    fn level_at_uses_buckets() {
+        let mut grids = LevelGrids::default();
+        for (i, origin) in [Vec2::ZERO, Vec2::new(128.0, 0.0), Vec2::new(0.0, -256.0)]
+            .into_iter()
+            .enumerate()
+        {
+            let iid = format!("level {i}");
+            let grid = LevelGrid::new(
+                iid.clone(),
+                iid.clone(),
+                Entity::from_raw(i as u32),
+                origin,
+                Vector2I::new(16, 32),
+            );
+            grids.insert(grid);
+        }
+
+        let level_at = |position| grids.level_at(position).map(|grid| grid.iid.as_str());
+        assert_eq!(level_at(Vec2::new(10.0, 10.0)), Some("level 0"));
+        assert_eq!(level_at(Vec2::new(10.0, 250.0)), Some("level 0"));
+        assert_eq!(level_at(Vec2::new(200.0, 130.0)), Some("level 1"));
+        assert_eq!(level_at(Vec2::new(5.0, -1.0)), Some("level 2"));
+        assert_eq!(level_at(Vec2::new(300.0, 10.0)), None);
+        assert_eq!(level_at(Vec2::new(-1.0, 10.0)), None);
+    }
+}
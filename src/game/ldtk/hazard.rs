@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{merge_wall_rects, WallLayer};
+use crate::game::health::Hazard;
+
+/// IntGrid value of spike tiles
+pub const SPIKES_VALUE: i32 = 2;
+/// Damage dealt by touching spikes
+pub const SPIKES_DAMAGE: u32 = 1;
+
+#[derive(Bundle, LdtkIntCell, Default, Clone, Debug)]
+pub struct SpikesBundle {
+    spikes: Spikes,
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Spikes;
+
+/// Marks a sensor spawned by [`hazard_setup`], so it can be replaced when its region changes
+#[derive(Component, Clone, Copy, Debug)]
+pub struct HazardCollider {
+    pub layer: Entity,
+    pub region: u32,
+}
+
+/// Spike tiles of every IntGrid layer
+#[derive(Resource, Default)]
+pub struct HazardLayers {
+    pub layers: HashMap<Entity, WallLayer>,
+}
+
+/// Spawns hazard sensors for the spike tiles of a level.
+///
+/// Works like [`super::wall_setup`]: tiles are merged into rectangles with [`merge_wall_rects`],
+/// but each rectangle becomes a [`Hazard`] sensor instead of a solid collider.
+/// Spike tiles don't have a tileset of their own, so the sensors are drawn as plain sprites.
+pub fn hazard_setup(
+    mut commands: Commands,
+    spikes_query: Query<(Entity, &GridCoords, &Parent), Added<Spikes>>,
+    layer_query: Query<(&LayerMetadata, &Parent), Without<Spikes>>,
+    collider_query: Query<(Entity, &HazardCollider)>,
+    mut hazard_layers: ResMut<HazardLayers>,
+) {
+    spikes_query.for_each(|(entity, &grid_coords, parent)| {
+        hazard_layers
+            .layers
+            .entry(parent.get())
+            .or_default()
+            .add_tile(entity, grid_coords);
+    });
+
+    hazard_layers
+        .layers
+        .retain(|layer_entity, _| layer_query.contains(*layer_entity));
+
+    for (layer_entity, hazard_layer) in hazard_layers.layers.iter_mut() {
+        if hazard_layer.dirty.is_empty() {
+            continue;
+        }
+
+        if let Ok((layer, level)) = layer_query.get(*layer_entity) {
+            let regions = hazard_layer.take_dirty_regions();
+
+            for (collider_entity, hazard_collider) in collider_query.iter() {
+                if hazard_collider.layer == *layer_entity
+                    && regions.removed.contains(&hazard_collider.region)
+                {
+                    commands.entity(collider_entity).despawn_recursive();
+                }
+            }
+
+            let grid_size = layer.grid_size as f32;
+            commands.entity(level.get()).with_children(|level| {
+                for (region, tiles) in regions.added.iter() {
+                    for rect in merge_wall_rects(tiles, layer.c_wid, layer.c_hei) {
+                        let size = Vec2::new(rect.width() as f32, rect.height() as f32) * grid_size;
+                        level.spawn((
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::rgb(0.82, 0.23, 0.23),
+                                    custom_size: Some(size),
+                                    ..default()
+                                },
+                                transform: Transform::from_xyz(
+                                    (rect.left + rect.right + 1) as f32 * grid_size / 2.,
+                                    (rect.bottom + rect.top + 1) as f32 * grid_size / 2.,
+                                    // Above the background and the IntGrid layer
+                                    1.5,
+                                ),
+                                ..default()
+                            },
+                            // Slightly smaller than the tiles, so walking next to spikes is safe
+                            Collider::cuboid(size.x / 2. - 0.5, size.y / 2. - 0.5),
+                            Sensor,
+                            RigidBody::Fixed,
+                            ActiveCollisionTypes::KINEMATIC_STATIC,
+                            Hazard {
+                                damage: SPIKES_DAMAGE,
+                            },
+                            HazardCollider {
+                                layer: *layer_entity,
+                                region: *region,
+                            },
+                        ));
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Marks removed spike tiles for [`hazard_setup`] to rebuild, see [`super::wall_removal_tracker`]
+pub fn hazard_removal_tracker(
+    removed: RemovedComponents<Spikes>,
+    mut hazard_layers: ResMut<HazardLayers>,
+) {
+    for entity in removed.iter() {
+        for hazard_layer in hazard_layers.layers.values_mut() {
+            if hazard_layer.remove_tile(entity) {
+                break;
+            }
+        }
+    }
+}
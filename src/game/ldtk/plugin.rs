@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{
+    app, prelude::*, systems, LdtkLevelLoader, LdtkLoader, LdtkStage, LdtkSystemLabel,
+};
+use bevy_ecs_tilemap::TilemapPlugin;
+
+/// [`LdtkPlugin`] with parts of it left out.
+///
+/// The systems and resources are the same as the ones of [`LdtkPlugin`], which can't be
+/// configured.
+pub struct LdtkPluginSetup {
+    /// Derive the [`LevelSet`] from the [`LevelSelection`] every frame, like [`LdtkPlugin`] does.
+    ///
+    /// Turn this off when something else decides which levels are spawned, like
+    /// [`crate::game::level_streaming`]. Otherwise both fight over the [`LevelSet`], and the
+    /// [`ClearColor`] is set from the selected level every time it's overwritten.
+    pub level_set_from_selection: bool,
+    /// Add the [`TilemapPlugin`] to draw the tile layers.
+    ///
+    /// It needs a render device, so it's left out when running headless, see
+    /// [`crate::game::default_plugin_setup::HeadlessPluginSetup`]. The tilemaps are still spawned.
+    pub render: bool,
+}
+
+impl Default for LdtkPluginSetup {
+    fn default() -> Self {
+        Self {
+            level_set_from_selection: true,
+            render: true,
+        }
+    }
+}
+
+impl Plugin for LdtkPluginSetup {
+    fn build(&self, app: &mut App) {
+        if self.render && !app.is_plugin_added::<TilemapPlugin>() {
+            app.add_plugin(TilemapPlugin);
+        }
+
+        app.add_stage_after(
+            CoreStage::Update,
+            LdtkStage::ProcessApi,
+            SystemStage::parallel(),
+        )
+        .init_non_send_resource::<app::LdtkEntityMap>()
+        .init_non_send_resource::<app::LdtkIntCellMap>()
+        .init_resource::<LdtkSettings>()
+        .add_asset::<LdtkAsset>()
+        .init_asset_loader::<LdtkLoader>()
+        .add_asset::<LdtkLevel>()
+        .init_asset_loader::<LdtkLevelLoader>()
+        .add_event::<LevelEvent>()
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            systems::process_ldtk_assets.label(LdtkSystemLabel::ProcessAssets),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            systems::process_ldtk_levels.label(LdtkSystemLabel::LevelSpawning),
+        )
+        .add_system_to_stage(
+            LdtkStage::ProcessApi,
+            systems::worldly_adoption.label(LdtkSystemLabel::Other),
+        )
+        .add_system_to_stage(
+            LdtkStage::ProcessApi,
+            systems::apply_level_set
+                .label(LdtkSystemLabel::LevelSet)
+                .after(LdtkSystemLabel::LevelSelection),
+        )
+        .add_system_to_stage(
+            LdtkStage::ProcessApi,
+            systems::clean_respawn_entities.at_end(),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            systems::detect_level_spawned_events
+                .pipe(systems::fire_level_transformed_events)
+                .label(LdtkSystemLabel::Other),
+        );
+
+        if self.level_set_from_selection {
+            app.add_system_to_stage(
+                LdtkStage::ProcessApi,
+                systems::apply_level_selection.label(LdtkSystemLabel::LevelSelection),
+            );
+        }
+    }
+}
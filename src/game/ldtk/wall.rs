@@ -0,0 +1,1066 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use std::collections::HashSet;
+
+#[derive(Bundle, LdtkIntCell, Default, Clone, Debug)]
+pub struct WallBundle {
+    wall: Wall,
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Wall;
+
+/// Marks a collider spawned by [`wall_setup`], so it can be replaced when its region changes
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WallCollider {
+    pub layer: Entity,
+    pub region: u32,
+}
+
+/// Wall tiles of every IntGrid layer
+#[derive(Resource, Default)]
+pub struct WallLayers {
+    pub layers: HashMap<Entity, WallLayer>,
+}
+
+/// Wall tiles of a single IntGrid layer. Also used for other merged tiles, like hazards.
+///
+/// Colliders are built per region of connected tiles, so changing a tile only rebuilds the
+/// colliders of the regions it touches. Neither rectangles nor outlines ever span two regions,
+/// so the colliders are the same as when building the whole layer at once.
+#[derive(Default)]
+pub struct WallLayer {
+    pub walls: HashSet<GridCoords>,
+    /// Wall tile entity to its position, used to find the position of removed walls
+    tiles: HashMap<Entity, GridCoords>,
+    /// Number of tile entities in each position, a position stays a wall until all of them are gone
+    tile_counts: HashMap<GridCoords, u32>,
+    /// Positions where tiles have been added or removed since the colliders were last built
+    pub(super) dirty: HashSet<GridCoords>,
+    /// Region of each tile, as of when the colliders were last built
+    regions: HashMap<GridCoords, u32>,
+    /// Tiles of each region, as of when the colliders were last built
+    region_tiles: HashMap<u32, HashSet<GridCoords>>,
+    next_region: u32,
+}
+
+/// Regions of a [`WallLayer`] whose colliders have to be rebuilt
+#[derive(Default, Debug)]
+pub struct DirtyRegions {
+    /// Regions which don't exist anymore, their colliders have to be despawned
+    pub removed: Vec<u32>,
+    /// Regions to spawn colliders for, along with their tiles
+    pub added: Vec<(u32, HashSet<GridCoords>)>,
+}
+
+impl WallLayer {
+    pub(super) fn add_tile(&mut self, entity: Entity, coords: GridCoords) {
+        if let Some(old) = self.tiles.insert(entity, coords) {
+            self.forget_tile(old);
+        }
+        *self.tile_counts.entry(coords).or_default() += 1;
+        self.walls.insert(coords);
+        self.dirty.insert(coords);
+    }
+
+    pub(super) fn remove_tile(&mut self, entity: Entity) -> bool {
+        match self.tiles.remove(&entity) {
+            Some(coords) => {
+                self.forget_tile(coords);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn forget_tile(&mut self, coords: GridCoords) {
+        if let Some(count) = self.tile_counts.get_mut(&coords) {
+            *count -= 1;
+            if *count == 0 {
+                self.tile_counts.remove(&coords);
+                self.walls.remove(&coords);
+            }
+        }
+        self.dirty.insert(coords);
+    }
+
+    /// Takes the regions touching the changed tiles, and splits their tiles into new regions.
+    ///
+    /// A removed tile can split its region in two, and an added one can join several regions into
+    /// one, so every region next to a changed tile is replaced.
+    pub(super) fn take_dirty_regions(&mut self) -> DirtyRegions {
+        let mut dirty: Vec<GridCoords> = self.dirty.drain().collect();
+        dirty.sort_by_key(|coords| (coords.y, coords.x));
+
+        let mut regions = DirtyRegions::default();
+        let mut seeds = Vec::new();
+        for coords in dirty {
+            for neighbour in [coords].into_iter().chain(wall_neighbours(coords)) {
+                if let Some(region) = self.regions.get(&neighbour).copied() {
+                    if let Some(tiles) = self.region_tiles.remove(&region) {
+                        for tile in tiles.iter() {
+                            self.regions.remove(tile);
+                        }
+                        seeds.extend(tiles);
+                        regions.removed.push(region);
+                    }
+                }
+            }
+            seeds.push(coords);
+        }
+        seeds.sort_by_key(|coords| (coords.y, coords.x));
+
+        for tiles in connected_walls(&self.walls, seeds) {
+            let region = self.next_region;
+            self.next_region += 1;
+            for tile in tiles.iter() {
+                self.regions.insert(*tile, region);
+            }
+            self.region_tiles.insert(region, tiles.clone());
+            regions.added.push((region, tiles));
+        }
+        regions
+    }
+}
+
+fn wall_neighbours(GridCoords { x, y }: GridCoords) -> [GridCoords; 4] {
+    [
+        GridCoords { x: x + 1, y },
+        GridCoords { x: x - 1, y },
+        GridCoords { x, y: y + 1 },
+        GridCoords { x, y: y - 1 },
+    ]
+}
+
+/// Splits the walls reachable from the seeds into regions of tiles connected by their sides.
+///
+/// Tiles only touching diagonally end up in different regions, like [`trace_wall_outlines`] keeps
+/// them in different outlines. Seeds which aren't walls are skipped.
+pub fn connected_walls(
+    walls: &HashSet<GridCoords>,
+    seeds: impl IntoIterator<Item = GridCoords>,
+) -> Vec<HashSet<GridCoords>> {
+    let mut visited: HashSet<GridCoords> = HashSet::new();
+    let mut regions = Vec::new();
+    for seed in seeds {
+        if !walls.contains(&seed) || !visited.insert(seed) {
+            continue;
+        }
+        let mut region = HashSet::new();
+        let mut stack = vec![seed];
+        while let Some(coords) = stack.pop() {
+            region.insert(coords);
+            for neighbour in wall_neighbours(coords) {
+                if walls.contains(&neighbour) && visited.insert(neighbour) {
+                    stack.push(neighbour);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+/// How colliders are generated for the walls of an IntGrid layer
+#[derive(Reflect, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum WallColliderMode {
+    /// Cuboid for each merged rectangle of walls. Actors can catch on the seams between them.
+    #[default]
+    Rectangles,
+    /// Closed polylines along the outlines of the walls, so there are no internal seams
+    Outline,
+}
+
+/// Chooses the [`WallColliderMode`] for each IntGrid layer by its identifier.
+///
+/// Set through [`crate::game::SigilConfig::wall_colliders`].
+#[derive(Resource, Clone, Default, Debug)]
+pub struct WallColliderSettings {
+    pub default_mode: WallColliderMode,
+    pub layer_modes: HashMap<String, WallColliderMode>,
+}
+
+impl WallColliderSettings {
+    pub fn mode(&self, layer_identifier: &str) -> WallColliderMode {
+        self.layer_modes
+            .get(layer_identifier)
+            .copied()
+            .unwrap_or(self.default_mode)
+    }
+}
+
+/// A rectangle of wall tiles, bounds are inclusive and in grid coordinates
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Hash)]
+pub struct WallRect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl WallRect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.top - self.bottom + 1
+    }
+
+    pub fn area(&self) -> i32 {
+        self.width() * self.height()
+    }
+}
+
+/// Splits the given wall tiles into non-overlapping rectangles which exactly cover the tiles.
+///
+/// Two decompositions are made, and the one with fewer rectangles is kept:
+/// - [`merge_wall_rows`], which handles wide floors and ceilings well
+/// - a greedy one, which does better on staircases, pillars and notched walls:
+///     1. go through the tiles row by row, starting from the bottom left
+///     2. every tile not yet covered starts a new rectangle
+///     3. grow the rectangle both "wide first" and "tall first", keeping the larger one
+///     4. mark the tiles of the rectangle as covered
+///
+/// So the result never has more rectangles than merging rows, though it isn't always the minimum.
+/// `benches/wall_colliders.rs` compares the collider counts on the rooms of the world.
+/// Cheap enough to run whenever a level spawns.
+pub fn merge_wall_rects(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<WallRect> {
+    let greedy = merge_wall_rects_greedy(walls, width, height);
+    let rows = merge_wall_rows(walls, width, height);
+    if rows.len() < greedy.len() {
+        rows
+    } else {
+        greedy
+    }
+}
+
+/// Bounding box of the tiles inside of the layer, `None` without any.
+///
+/// Only the bounding box has to be searched, e.g. a single wall region of a large layer.
+fn wall_bounds(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Option<(IVec2, IVec2)> {
+    walls
+        .iter()
+        .filter(|coords| coords.x >= 0 && coords.x < width && coords.y >= 0 && coords.y < height)
+        .map(|coords| IVec2::new(coords.x, coords.y))
+        .fold(None, |bounds, coords| match bounds {
+            Some((min, max)) => Some((coords.min(min), coords.max(max))),
+            None => Some((coords, coords)),
+        })
+}
+
+/// Merges the tiles of every row into horizontal runs, then stacks runs with the same span on
+/// consecutive rows into rectangles. This is the approach of the bevy_ecs_ldtk example.
+pub fn merge_wall_rows(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<WallRect> {
+    let (min, max) = match wall_bounds(walls, width, height) {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
+
+    let mut rects: Vec<WallRect> = Vec::new();
+    // Rectangles which reach up to the previous row, and may continue on the next one
+    let mut open: Vec<WallRect> = Vec::new();
+    for y in min.y..=max.y {
+        let mut runs: Vec<(i32, i32)> = Vec::new();
+        let mut start = None;
+        for x in min.x..=max.x + 1 {
+            match (walls.contains(&GridCoords { x, y }) && x <= max.x, start) {
+                (true, None) => start = Some(x),
+                (false, Some(left)) => {
+                    runs.push((left, x - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        let mut next_open = Vec::with_capacity(runs.len());
+        for (left, right) in runs {
+            match open
+                .iter()
+                .position(|rect| rect.left == left && rect.right == right)
+            {
+                Some(index) => {
+                    let mut rect = open.swap_remove(index);
+                    rect.top = y;
+                    next_open.push(rect);
+                }
+                None => next_open.push(WallRect {
+                    left,
+                    right,
+                    bottom: y,
+                    top: y,
+                }),
+            }
+        }
+        // Runs which don't continue on this row are finished
+        rects.append(&mut open);
+        open = next_open;
+    }
+    rects.append(&mut open);
+    rects
+}
+
+fn merge_wall_rects_greedy(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<WallRect> {
+    let mut covered: HashSet<GridCoords> = HashSet::new();
+    let mut rects: Vec<WallRect> = Vec::new();
+
+    let is_free = |covered: &HashSet<GridCoords>, coords: GridCoords| {
+        walls.contains(&coords) && !covered.contains(&coords)
+    };
+
+    let (min, max) = match wall_bounds(walls, width, height) {
+        Some(bounds) => bounds,
+        None => return rects,
+    };
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            if !is_free(&covered, GridCoords { x, y }) {
+                continue;
+            }
+
+            let row_free = |covered: &HashSet<GridCoords>, y: i32, left: i32, right: i32| {
+                (left..=right).all(|x| is_free(covered, GridCoords { x, y }))
+            };
+            let column_free = |covered: &HashSet<GridCoords>, x: i32, bottom: i32, top: i32| {
+                (bottom..=top).all(|y| is_free(covered, GridCoords { x, y }))
+            };
+
+            // Wide first: extend right, then up as long as the whole span is free
+            let mut wide = WallRect {
+                left: x,
+                right: x,
+                bottom: y,
+                top: y,
+            };
+            while wide.right + 1 < width
+                && is_free(
+                    &covered,
+                    GridCoords {
+                        x: wide.right + 1,
+                        y,
+                    },
+                )
+            {
+                wide.right += 1;
+            }
+            while wide.top + 1 < height && row_free(&covered, wide.top + 1, wide.left, wide.right) {
+                wide.top += 1;
+            }
+
+            // Tall first: extend up, then right as long as the whole span is free
+            let mut tall = WallRect {
+                left: x,
+                right: x,
+                bottom: y,
+                top: y,
+            };
+            while tall.top + 1 < height && is_free(&covered, GridCoords { x, y: tall.top + 1 }) {
+                tall.top += 1;
+            }
+            while tall.right + 1 < width
+                && column_free(&covered, tall.right + 1, tall.bottom, tall.top)
+            {
+                tall.right += 1;
+            }
+
+            let rect = if tall.area() > wide.area() {
+                tall
+            } else {
+                wide
+            };
+
+            for cover_y in rect.bottom..=rect.top {
+                for cover_x in rect.left..=rect.right {
+                    covered.insert(GridCoords {
+                        x: cover_x,
+                        y: cover_y,
+                    });
+                }
+            }
+            rects.push(rect);
+        }
+    }
+
+    rects
+}
+
+/// Traces the outlines of the given wall tiles into closed loops of grid corner points.
+///
+/// Every outline goes counter-clockwise around the solid tiles (and so clockwise around holes),
+/// with collinear points removed. The first point is not repeated at the end.
+///
+/// Only the edges between a wall tile and an empty tile end up in the outline, so actors moving
+/// along the wall never touch an internal edge between two tiles.
+pub fn trace_wall_outlines(
+    walls: &HashSet<GridCoords>,
+    width: i32,
+    height: i32,
+) -> Vec<Vec<IVec2>> {
+    let is_wall = |x: i32, y: i32| {
+        x >= 0 && x < width && y >= 0 && y < height && walls.contains(&GridCoords { x, y })
+    };
+
+    // Collect every directed boundary edge, keeping the solid tile on the left side
+    let mut edges: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    for coords in walls.iter() {
+        let GridCoords { x, y } = *coords;
+        if !is_wall(x, y) {
+            continue;
+        }
+        let mut add_edge = |from: IVec2, to: IVec2| {
+            edges.entry(from).or_default().push(to);
+        };
+        if !is_wall(x, y - 1) {
+            add_edge(IVec2::new(x, y), IVec2::new(x + 1, y));
+        }
+        if !is_wall(x + 1, y) {
+            add_edge(IVec2::new(x + 1, y), IVec2::new(x + 1, y + 1));
+        }
+        if !is_wall(x, y + 1) {
+            add_edge(IVec2::new(x + 1, y + 1), IVec2::new(x, y + 1));
+        }
+        if !is_wall(x - 1, y) {
+            add_edge(IVec2::new(x, y + 1), IVec2::new(x, y));
+        }
+    }
+
+    // Start from the lowest point so the result doesn't depend on HashMap ordering
+    let mut starts: Vec<IVec2> = edges.keys().copied().collect();
+    starts.sort_by_key(|point| (point.y, point.x));
+
+    // As many edges arrive at every point as leave it, so all of them form closed loops
+    let mut outlines = Vec::new();
+    for start in starts.iter() {
+        while edges.get(start).map_or(false, |ends| !ends.is_empty()) {
+            outlines.push(trace_outline(*start, &mut edges));
+        }
+    }
+
+    outlines
+}
+
+/// Follows the edges from the start until getting back to it
+fn trace_outline(start: IVec2, edges: &mut HashMap<IVec2, Vec<IVec2>>) -> Vec<IVec2> {
+    let mut points = vec![start];
+    let mut first_direction = None;
+    let mut direction = IVec2::ZERO;
+    let mut current = start;
+    loop {
+        // Where two tiles only touch diagonally, there are two ways to continue.
+        // Turning left keeps following the same tile, so the regions are kept separate.
+        let next = edges.get_mut(&current).and_then(|ends| {
+            let index = (0..ends.len()).max_by_key(|i| direction.perp_dot(ends[*i] - current))?;
+            Some(ends.swap_remove(index))
+        });
+        let next = match next {
+            Some(next) => next,
+            None => break,
+        };
+
+        let next_direction = next - current;
+        if next_direction != direction && direction != IVec2::ZERO {
+            points.push(current);
+        }
+        first_direction.get_or_insert(next_direction);
+        direction = next_direction;
+        current = next;
+        if current == start {
+            break;
+        }
+    }
+
+    // The start point is redundant if the loop ends going the same way it started
+    if first_direction == Some(direction) {
+        points.remove(0);
+    }
+    points
+}
+
+/// Wall collider system, originally from the bevy_ecs_ldtk example.
+///
+/// Spawns colliders for the walls of a level
+///
+/// You could just insert a ColliderBundle in to the WallBundle,
+/// but this spawns a different collider for EVERY wall tile.
+/// This approach leads to bad performance.
+///
+/// Instead, by flagging the wall tiles and spawning the collisions later,
+/// we can minimize the amount of colliding entities.
+///
+/// In basic terms, it will:
+/// 1. consider where the walls are, and which regions of connected walls were changed
+/// 2. despawn the colliders of the changed regions
+/// 3. depending on the [`WallColliderMode`] of the layer, either
+///     - combine wall tiles into as few rectangles as reasonably possible (see [`merge_wall_rects`])
+///     - trace the outlines of the walls (see [`trace_wall_outlines`])
+/// 4. spawn colliders for each rectangle or outline
+///
+/// Walls can be added and removed at runtime (e.g. breakable blocks) by inserting or removing the
+/// [`Wall`] component of an IntGrid tile. Only the regions touching the changed tiles are rebuilt,
+/// see [`WallLayer::take_dirty_regions`].
+/// The old colliders are despawned with the same commands the new ones are spawned with,
+/// so actors standing on an unchanged part of the wall never lose their ground.
+pub fn wall_setup(
+    mut commands: Commands,
+    wall_query: Query<(Entity, &GridCoords, &Parent), Added<Wall>>,
+    layer_query: Query<(&LayerMetadata, &Parent), Without<Wall>>,
+    collider_query: Query<(Entity, &WallCollider)>,
+    settings: Res<WallColliderSettings>,
+    mut wall_layers: ResMut<WallLayers>,
+) {
+    // Consider where the walls are
+    // storing them as GridCoords in a HashSet for quick, easy lookup
+    //
+    // The key of this map will be the layer entity the wall belongs to.
+    // This has two consequences in the resulting collision entities:
+    // 1. it forces the walls to be split along level boundaries
+    // 2. it lets us easily add the collision entities as children of the appropriate level entity
+    wall_query.for_each(|(entity, &grid_coords, parent)| {
+        wall_layers
+            .layers
+            .entry(parent.get())
+            .or_default()
+            .add_tile(entity, grid_coords);
+    });
+
+    // Forget the layers of despawned levels, their colliders are already gone with the level
+    wall_layers
+        .layers
+        .retain(|layer_entity, _| layer_query.contains(*layer_entity));
+
+    for (layer_entity, wall_layer) in wall_layers.layers.iter_mut() {
+        if wall_layer.dirty.is_empty() {
+            continue;
+        }
+
+        // An intgrid tile's direct parent will be a layer entity, not the level entity
+        // To get the level entity, you need the tile's grandparent.
+        // This is where layer_query comes in.
+        if let Ok((layer, level)) = layer_query.get(*layer_entity) {
+            let regions = wall_layer.take_dirty_regions();
+
+            for (collider_entity, wall_collider) in collider_query.iter() {
+                if wall_collider.layer == *layer_entity
+                    && regions.removed.contains(&wall_collider.region)
+                {
+                    commands.entity(collider_entity).despawn_recursive();
+                }
+            }
+
+            let mode = settings.mode(&layer.identifier);
+            commands.entity(level.get()).with_children(|level| {
+                // Spawn colliders for every region..
+                // Making the collider a child of the level serves two purposes:
+                // 1. Adjusts the transforms to be relative to the level for free
+                // 2. the colliders will be despawned automatically when levels unload
+                for (region, tiles) in regions.added.iter() {
+                    spawn_wall_colliders(
+                        level,
+                        tiles,
+                        layer,
+                        mode,
+                        WallCollider {
+                            layer: *layer_entity,
+                            region: *region,
+                        },
+                    );
+                }
+            });
+
+            debug!(
+                "Rebuilt {count} wall regions in layer {layer}",
+                count = regions.added.len(),
+                layer = layer.identifier,
+            );
+        }
+    }
+}
+
+/// Marks removed walls for [`wall_setup`] to rebuild.
+///
+/// Removed components are only visible for the rest of the frame they are removed in,
+/// so this has to run late in the frame. The colliders are rebuilt at the start of the next one.
+pub fn wall_removal_tracker(removed: RemovedComponents<Wall>, mut wall_layers: ResMut<WallLayers>) {
+    for entity in removed.iter() {
+        for wall_layer in wall_layers.layers.values_mut() {
+            if wall_layer.remove_tile(entity) {
+                break;
+            }
+        }
+    }
+}
+
+fn spawn_wall_colliders(
+    level: &mut ChildBuilder,
+    tiles: &HashSet<GridCoords>,
+    layer: &LayerMetadata,
+    mode: WallColliderMode,
+    marker: WallCollider,
+) {
+    let grid_size = layer.grid_size as f32;
+    match mode {
+        WallColliderMode::Rectangles => {
+            for wall_rect in merge_wall_rects(tiles, layer.c_wid, layer.c_hei) {
+                level.spawn((
+                    Collider::cuboid(
+                        wall_rect.width() as f32 * grid_size / 2.,
+                        wall_rect.height() as f32 * grid_size / 2.,
+                    ),
+                    RigidBody::Fixed,
+                    Friction::new(1.0),
+                    TransformBundle::from_transform(Transform::from_xyz(
+                        (wall_rect.left + wall_rect.right + 1) as f32 * grid_size / 2.,
+                        (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size / 2.,
+                        0.,
+                    )),
+                    marker,
+                ));
+            }
+        }
+        WallColliderMode::Outline => {
+            for outline in trace_wall_outlines(tiles, layer.c_wid, layer.c_hei) {
+                let vertices: Vec<Vec2> = outline
+                    .iter()
+                    .map(|point| point.as_vec2() * grid_size)
+                    .collect();
+                // Close the loop by connecting the last point back to the first one
+                let count = vertices.len() as u32;
+                let indices = (0..count).map(|i| [i, (i + 1) % count]).collect();
+
+                level.spawn((
+                    Collider::polyline(vertices, Some(indices)),
+                    RigidBody::Fixed,
+                    Friction::new(1.0),
+                    TransformBundle::default(),
+                    marker,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wall tiles from rows of `#` (wall) and `.` (empty), the first row being the top one
+    fn grid(rows: &[&str]) -> (HashSet<GridCoords>, i32, i32) {
+        let height = rows.len() as i32;
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0) as i32;
+        let walls = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '#')
+                    .map(move |(x, _)| GridCoords {
+                        x: x as i32,
+                        y: height - 1 - row as i32,
+                    })
+            })
+            .collect();
+        (walls, width, height)
+    }
+
+    /// Small linear congruential generator, so the random grids are the same on every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as u32
+        }
+
+        fn grid(&mut self, width: i32, height: i32, density: u32) -> HashSet<GridCoords> {
+            let mut walls = HashSet::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if self.next() % 100 < density {
+                        walls.insert(GridCoords { x, y });
+                    }
+                }
+            }
+            walls
+        }
+    }
+
+    fn assert_exact_cover(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<WallRect> {
+        let rects = merge_wall_rects(walls, width, height);
+        let mut covered = HashSet::new();
+        for rect in rects.iter() {
+            assert!(
+                rect.left <= rect.right && rect.bottom <= rect.top,
+                "{rect:?}"
+            );
+            for y in rect.bottom..=rect.top {
+                for x in rect.left..=rect.right {
+                    let coords = GridCoords { x, y };
+                    assert!(
+                        walls.contains(&coords),
+                        "{rect:?} covers empty tile {coords:?}"
+                    );
+                    assert!(covered.insert(coords), "{coords:?} is covered twice");
+                }
+            }
+        }
+        assert_eq!(&covered, walls, "every wall tile should be covered");
+        rects
+    }
+
+    #[test]
+    fn merge_empty() {
+        let (walls, width, height) = grid(&["....", "...."]);
+        assert!(assert_exact_cover(&walls, width, height).is_empty());
+    }
+
+    #[test]
+    fn merge_full_rectangle() {
+        let (walls, width, height) = grid(&["#####", "#####", "#####"]);
+        let rects = assert_exact_cover(&walls, width, height);
+        assert_eq!(
+            rects,
+            vec![WallRect {
+                left: 0,
+                right: 4,
+                bottom: 0,
+                top: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn merge_hand_made() {
+        let grids: [&[&str]; 6] = [
+            // Staircase
+            &["#...", "##..", "###.", "####"],
+            // Room with a hole in the middle
+            &["#####", "#...#", "#...#", "#####"],
+            // Notch in a floor
+            &["........", "###..###", "########"],
+            // Tiles touching diagonally
+            &["#.#.", ".#.#", "#.#."],
+            // Tall pillar next to a wide floor
+            &["#.....", "#.....", "#.....", "######"],
+            // Single tiles
+            &["#", "."],
+        ];
+        for rows in grids {
+            let (walls, width, height) = grid(rows);
+            assert_exact_cover(&walls, width, height);
+        }
+    }
+
+    #[test]
+    fn merge_keeps_the_better_decomposition() {
+        // A wall with notches on its side is a rectangle for every row when merging rows
+        let (walls, width, height) = grid(&["##", "#.", "##", "#.", "##"]);
+        assert_eq!(merge_wall_rows(&walls, width, height).len(), 5);
+        assert_eq!(assert_exact_cover(&walls, width, height).len(), 4);
+
+        // Growing the stem of a T upwards first would split its top, so the rows are kept
+        let (walls, width, height) = grid(&["###", ".#."]);
+        assert_eq!(merge_wall_rects_greedy(&walls, width, height).len(), 3);
+        assert_eq!(assert_exact_cover(&walls, width, height).len(), 2);
+    }
+
+    #[test]
+    fn merge_rows_exact_cover() {
+        let mut rng = Lcg(11);
+        for density in [10, 50, 80, 95] {
+            for _ in 0..50 {
+                let width = 1 + (rng.next() % 24) as i32;
+                let height = 1 + (rng.next() % 24) as i32;
+                let walls = rng.grid(width, height, density);
+                let rects = merge_wall_rows(&walls, width, height);
+                let covered: usize = rects.iter().map(|rect| rect.area() as usize).sum();
+                assert_eq!(covered, walls.len());
+                for rect in rects {
+                    for y in rect.bottom..=rect.top {
+                        for x in rect.left..=rect.right {
+                            assert!(walls.contains(&GridCoords { x, y }), "{rect:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merge_world_rooms_never_worse_than_rows() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/levels/world.ldtk");
+        let json = std::fs::read_to_string(path).unwrap();
+        let world: bevy_ecs_ldtk::ldtk::LdtkJson = serde_json::from_str(&json).unwrap();
+
+        let mut rooms = 0;
+        for level in world.levels {
+            for layer in level.layer_instances.unwrap_or_default() {
+                if layer.layer_instance_type != bevy_ecs_ldtk::ldtk::Type::IntGrid {
+                    continue;
+                }
+                // The csv goes row by row from the top, grid coordinates go up from the bottom
+                let walls: HashSet<GridCoords> = layer
+                    .int_grid_csv
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value == 1)
+                    .map(|(i, _)| GridCoords {
+                        x: i as i32 % layer.c_wid,
+                        y: layer.c_hei - 1 - i as i32 / layer.c_wid,
+                    })
+                    .collect();
+                let rects = assert_exact_cover(&walls, layer.c_wid, layer.c_hei);
+                let rows = merge_wall_rows(&walls, layer.c_wid, layer.c_hei);
+                assert!(
+                    rects.len() <= rows.len(),
+                    "{} has {} rects, {} when merging rows",
+                    level.identifier,
+                    rects.len(),
+                    rows.len()
+                );
+                rooms += 1;
+            }
+        }
+        assert!(rooms > 0);
+    }
+
+    #[test]
+    fn merge_random() {
+        let mut rng = Lcg(42);
+        for density in [10, 50, 80, 95] {
+            for _ in 0..50 {
+                let width = 1 + (rng.next() % 24) as i32;
+                let height = 1 + (rng.next() % 24) as i32;
+                let walls = rng.grid(width, height, density);
+                assert_exact_cover(&walls, width, height);
+            }
+        }
+    }
+
+    /// Twice the signed area of the loop, positive when counter-clockwise
+    fn double_area(outline: &[IVec2]) -> i32 {
+        (0..outline.len())
+            .map(|i| outline[i].perp_dot(outline[(i + 1) % outline.len()]))
+            .sum()
+    }
+
+    /// Checks that the loops are made of axis aligned segments without collinear points, and that
+    /// together they enclose exactly the wall tiles
+    fn assert_outlines(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<Vec<IVec2>> {
+        let outlines = trace_wall_outlines(walls, width, height);
+        let mut boundary_edges = 0;
+        for outline in outlines.iter() {
+            assert!(outline.len() >= 4, "{outline:?}");
+            for i in 0..outline.len() {
+                let previous = outline[(i + outline.len() - 1) % outline.len()];
+                let next = outline[(i + 1) % outline.len()];
+                let (a, b) = (outline[i] - previous, next - outline[i]);
+                assert!(a.x == 0 || a.y == 0, "diagonal segment in {outline:?}");
+                assert_ne!(a.perp_dot(b), 0, "collinear point in {outline:?}");
+                boundary_edges += (a.x + a.y).abs();
+            }
+        }
+        let area: i32 = outlines.iter().map(|outline| double_area(outline)).sum();
+        assert_eq!(area, 2 * walls.len() as i32);
+
+        let is_wall = |x, y| walls.contains(&GridCoords { x, y });
+        let expected_edges: i32 = walls
+            .iter()
+            .map(|GridCoords { x, y }| {
+                [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    .into_iter()
+                    .filter(|(dx, dy)| !is_wall(x + dx, y + dy))
+                    .count() as i32
+            })
+            .sum();
+        assert_eq!(boundary_edges, expected_edges);
+        outlines
+    }
+
+    #[test]
+    fn outline_square() {
+        let (walls, width, height) = grid(&["##", "##"]);
+        let outlines = assert_outlines(&walls, width, height);
+        assert_eq!(
+            outlines,
+            vec![vec![
+                IVec2::new(0, 0),
+                IVec2::new(2, 0),
+                IVec2::new(2, 2),
+                IVec2::new(0, 2),
+            ]]
+        );
+    }
+
+    #[test]
+    fn outline_with_holes() {
+        let (walls, width, height) = grid(&["#######", "#.#...#", "#.#.#.#", "#######"]);
+        let outlines = assert_outlines(&walls, width, height);
+        let mut areas: Vec<i32> = outlines
+            .iter()
+            .map(|outline| double_area(outline))
+            .collect();
+        areas.sort();
+        // Holes go clockwise
+        assert_eq!(areas, vec![-10, -4, 2 * 28]);
+    }
+
+    #[test]
+    fn outline_diagonal_tiles_stay_separate() {
+        let (walls, width, height) = grid(&["#.#", ".#.", "#.#"]);
+        let outlines = assert_outlines(&walls, width, height);
+        assert_eq!(outlines.len(), 5);
+        assert!(outlines.iter().all(|outline| outline.len() == 4));
+
+        // A ring of tiles only touching diagonally encloses an empty tile, which is no hole
+        let (walls, width, height) = grid(&[".#.", "#.#", ".#."]);
+        let outlines = assert_outlines(&walls, width, height);
+        assert_eq!(outlines.len(), 4);
+        assert!(outlines.iter().all(|outline| double_area(outline) == 2));
+    }
+
+    #[test]
+    fn outline_ignores_tiles_outside_of_layer() {
+        let (mut walls, width, height) = grid(&["#"]);
+        walls.insert(GridCoords { x: 5, y: 0 });
+        let outlines = trace_wall_outlines(&walls, width, height);
+        assert_eq!(outlines.len(), 1);
+    }
+
+    #[test]
+    fn outline_random() {
+        let mut rng = Lcg(7);
+        for density in [20, 50, 80] {
+            for _ in 0..50 {
+                let width = 1 + (rng.next() % 16) as i32;
+                let height = 1 + (rng.next() % 16) as i32;
+                let walls = rng.grid(width, height, density);
+                assert_outlines(&walls, width, height);
+            }
+        }
+    }
+
+    /// Builds a layer of the tiles, one entity per tile
+    fn wall_layer(walls: &HashSet<GridCoords>) -> WallLayer {
+        let mut layer = WallLayer::default();
+        let mut walls: Vec<GridCoords> = walls.iter().copied().collect();
+        walls.sort_by_key(|coords| (coords.y, coords.x));
+        for (i, coords) in walls.into_iter().enumerate() {
+            layer.add_tile(Entity::from_raw(i as u32), coords);
+        }
+        layer
+    }
+
+    #[test]
+    fn regions_build_the_same_colliders_as_the_whole_layer() {
+        let mut rng = Lcg(3);
+        for density in [30, 60, 90] {
+            let (width, height) = (40, 35);
+            let walls = rng.grid(width, height, density);
+            let regions = wall_layer(&walls).take_dirty_regions();
+            assert!(regions.removed.is_empty());
+
+            let mut rects = 0;
+            let mut outlines: Vec<Vec<IVec2>> = Vec::new();
+            let mut covered = HashSet::new();
+            for (_, tiles) in regions.added.iter() {
+                for tile in tiles {
+                    assert!(covered.insert(*tile), "{tile:?} is in two regions");
+                }
+                rects += merge_wall_rects(tiles, width, height).len();
+                outlines.extend(trace_wall_outlines(tiles, width, height));
+            }
+            assert_eq!(covered, walls);
+
+            let mut whole = trace_wall_outlines(&walls, width, height);
+            // Every outline starts from its lowest point, so they can be compared in any order
+            let key = |outline: &Vec<IVec2>| (outline[0].y, outline[0].x, outline.len());
+            whole.sort_by_key(key);
+            outlines.sort_by_key(key);
+            assert_eq!(outlines, whole);
+            assert!(rects <= merge_wall_rows(&walls, width, height).len());
+        }
+    }
+
+    #[test]
+    fn long_wall_has_one_outline() {
+        let (walls, width, height) = grid(&[
+            "...#..................................#..",
+            "#########################################",
+        ]);
+        let mut layer = wall_layer(&walls);
+        let regions = layer.take_dirty_regions();
+        assert_eq!(regions.added.len(), 1);
+        let outlines = trace_wall_outlines(&regions.added[0].1, width, height);
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].len(), 12);
+
+        // Breaking a block of the floor rebuilds it as two walls
+        layer.remove_tile(Entity::from_raw(20));
+        let regions = layer.take_dirty_regions();
+        assert_eq!(regions.removed.len(), 1);
+        assert_eq!(regions.added.len(), 2);
+        for (_, tiles) in regions.added.iter() {
+            assert_eq!(trace_wall_outlines(tiles, width, height).len(), 1);
+        }
+    }
+
+    #[test]
+    fn only_touched_regions_are_rebuilt() {
+        let (walls, _, _) = grid(&["##...##", "##...##"]);
+        let mut layer = wall_layer(&walls);
+        let first = layer.take_dirty_regions();
+        assert_eq!(first.added.len(), 2);
+        let left = first
+            .added
+            .iter()
+            .find(|(_, tiles)| tiles.contains(&GridCoords { x: 0, y: 0 }))
+            .unwrap()
+            .0;
+
+        // Bridging the gap on the right joins nothing, the left wall is left alone
+        layer.add_tile(Entity::from_raw(100), GridCoords { x: 4, y: 0 });
+        let regions = layer.take_dirty_regions();
+        assert_eq!(regions.removed.len(), 1);
+        assert!(!regions.removed.contains(&left));
+        assert_eq!(regions.added.len(), 1);
+        assert_eq!(regions.added[0].1.len(), 5);
+
+        // Closing the gap joins both walls into one
+        layer.add_tile(Entity::from_raw(101), GridCoords { x: 2, y: 0 });
+        layer.add_tile(Entity::from_raw(102), GridCoords { x: 3, y: 0 });
+        let regions = layer.take_dirty_regions();
+        assert_eq!(regions.removed.len(), 2);
+        assert_eq!(regions.added.len(), 1);
+        assert_eq!(regions.added[0].1.len(), 11);
+    }
+
+    #[test]
+    fn layer_tracks_tiles_per_position() {
+        let mut layer = WallLayer::default();
+        let coords = GridCoords { x: 3, y: 20 };
+        layer.add_tile(Entity::from_raw(0), coords);
+        layer.add_tile(Entity::from_raw(1), coords);
+        layer.add_tile(Entity::from_raw(2), GridCoords { x: -1, y: 0 });
+        assert_eq!(layer.take_dirty_regions().added.len(), 2);
+
+        // The position stays a wall until every tile entity in it is gone
+        assert!(layer.remove_tile(Entity::from_raw(0)));
+        assert!(layer.walls.contains(&coords));
+        assert!(!layer.remove_tile(Entity::from_raw(0)));
+        assert!(layer.remove_tile(Entity::from_raw(1)));
+        assert!(!layer.walls.contains(&coords));
+
+        let regions = layer.take_dirty_regions();
+        assert_eq!(regions.removed.len(), 1);
+        assert!(regions.added.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::{prelude::*, LdtkStage, LdtkSystemLabel};
+use std::collections::HashSet;
+
+use super::state::GameState;
+
+pub struct LevelStreamingPlugin;
+
+impl Plugin for LevelStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelStreamEvent>()
+            .init_resource::<LevelStreaming>()
+            .add_system_to_stage(
+                LdtkStage::ProcessApi,
+                level_streaming.before(LdtkSystemLabel::LevelSet),
+            )
+            .add_system_to_stage(LdtkStage::ProcessApi, level_clear_color)
+            .add_system(level_stream_loads);
+    }
+}
+
+/// Sent when a streamed in level has finished spawning, or when it stops being streamed in
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LevelStreamEvent {
+    Load(String),
+    Unload(String),
+}
+
+/// Decides which levels are spawned around the selected level.
+///
+/// Neighbouring levels are spawned ahead of time, so their tiles and colliders are ready by the
+/// time the player walks in. Levels that are no longer needed stay around for a while of play
+/// before despawning, so running back and forth across a room boundary doesn't respawn them.
+/// The time doesn't count while the game is paused.
+///
+/// This is the only thing writing to the [`LevelSet`], so the level set bevy_ecs_ldtk derives
+/// from the [`LevelSelection`] has to be turned off with
+/// [`LdtkPluginSetup::level_set_from_selection`](crate::game::ldtk::LdtkPluginSetup).
+/// The [`ClearColor`] is still set from the selected level, following
+/// [`LdtkSettings::set_clear_color`].
+#[derive(Resource)]
+pub struct LevelStreaming {
+    /// How many neighbour steps away from the selected level are kept loaded
+    pub neighbour_radius: usize,
+    /// How long a level that is no longer needed stays loaded, in seconds of play
+    pub unload_delay: f32,
+    /// Streamed in levels by iid, with how long they haven't been needed for
+    loaded: HashMap<String, f32>,
+}
+
+impl Default for LevelStreaming {
+    fn default() -> Self {
+        Self {
+            neighbour_radius: 1,
+            unload_delay: 2.0,
+            loaded: HashMap::new(),
+        }
+    }
+}
+
+impl LevelStreaming {
+    pub fn is_loaded(&self, level_iid: &str) -> bool {
+        self.loaded.contains_key(level_iid)
+    }
+
+    pub fn loaded_iids(&self) -> impl Iterator<Item = &String> {
+        self.loaded.keys()
+    }
+}
+
+/// Levels within `radius` neighbour steps of the given level, including itself
+fn levels_within(ldtk_asset: &LdtkAsset, level_iid: &str, radius: usize) -> HashSet<String> {
+    let mut found = HashSet::from([level_iid.to_string()]);
+    let mut frontier = vec![level_iid.to_string()];
+
+    for _ in 0..radius {
+        let mut next_frontier = Vec::new();
+        for iid in frontier.iter() {
+            if let Some(level) = ldtk_asset.get_level(&LevelSelection::Iid(iid.clone())) {
+                for neighbour in level.neighbours.iter() {
+                    if found.insert(neighbour.level_iid.clone()) {
+                        next_frontier.push(neighbour.level_iid.clone());
+                    }
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    found
+}
+
+/// Spawns the levels around the [`LevelSelection`] through the [`LevelSet`]
+fn level_streaming(
+    mut world_query: Query<(&Handle<LdtkAsset>, &mut LevelSet)>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_selection: Option<Res<LevelSelection>>,
+    mut streaming: ResMut<LevelStreaming>,
+    mut events: EventWriter<LevelStreamEvent>,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+) {
+    // Levels are still streamed in while loading or paused, but nothing is unloaded
+    let delta = if *state.current() == GameState::Playing {
+        time.delta_seconds()
+    } else {
+        0.0
+    };
+    let level_selection = match level_selection {
+        Some(level_selection) => level_selection,
+        None => return,
+    };
+
+    for (ldtk_handle, mut level_set) in world_query.iter_mut() {
+        let ldtk_asset = match ldtk_assets.get(ldtk_handle) {
+            Some(ldtk_asset) => ldtk_asset,
+            None => continue,
+        };
+        let current = match ldtk_asset.get_level(&level_selection) {
+            Some(level) => level,
+            None => continue,
+        };
+
+        for unneeded in streaming.loaded.values_mut() {
+            *unneeded += delta;
+        }
+        for iid in levels_within(ldtk_asset, &current.iid, streaming.neighbour_radius) {
+            streaming.loaded.insert(iid, 0.0);
+        }
+
+        let unload_delay = streaming.unload_delay;
+        streaming.loaded.retain(|iid, unneeded| {
+            let keep = *unneeded <= unload_delay;
+            if !keep {
+                events.send(LevelStreamEvent::Unload(iid.clone()));
+            }
+            keep
+        });
+
+        let iids: HashSet<String> = streaming.loaded.keys().cloned().collect();
+        if level_set.iids != iids {
+            level_set.iids = iids;
+        }
+    }
+}
+
+/// Sets the [`ClearColor`] from the background of the selected level when it changes, which
+/// bevy_ecs_ldtk only does while deriving the [`LevelSet`] itself
+fn level_clear_color(
+    world_query: Query<&Handle<LdtkAsset>>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_selection: Option<Res<LevelSelection>>,
+    ldtk_settings: Res<LdtkSettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut current: Local<Option<String>>,
+) {
+    if ldtk_settings.set_clear_color != SetClearColor::FromLevelBackground {
+        return;
+    }
+    let level_selection = match level_selection {
+        Some(level_selection) => level_selection,
+        None => return,
+    };
+    for ldtk_handle in world_query.iter() {
+        if let Some(level) = ldtk_assets
+            .get(ldtk_handle)
+            .and_then(|ldtk_asset| ldtk_asset.get_level(&level_selection))
+        {
+            if current.as_ref() != Some(&level.iid) {
+                *current = Some(level.iid.clone());
+                clear_color.0 = level.bg_color;
+            }
+        }
+    }
+}
+
+/// Tells when the streamed in levels have spawned
+fn level_stream_loads(
+    mut level_events: EventReader<LevelEvent>,
+    streaming: Res<LevelStreaming>,
+    mut events: EventWriter<LevelStreamEvent>,
+) {
+    for event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = event {
+            if streaming.is_loaded(iid) {
+                events.send(LevelStreamEvent::Load(iid.clone()));
+            }
+        }
+    }
+}
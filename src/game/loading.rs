@@ -0,0 +1,269 @@
+use bevy::{
+    app::AppExit,
+    asset::{Asset, HandleId, LoadState},
+    prelude::*,
+};
+use bevy_ecs_ldtk::prelude::*;
+
+use super::{
+    ldtk::LdtkEnum,
+    menu::UiFont,
+    state::{GameState, GameStateSettings},
+};
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RequiredAssets>()
+            .add_system_set(SystemSet::on_enter(GameState::Loading).with_system(loading_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading)
+                    .with_system(world_dependencies)
+                    .with_system(loading_progress.after(world_dependencies))
+                    .with_system(loading_failure_input.after(loading_progress))
+                    .with_system(loading_screen_update.after(loading_failure_input)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(loading_cleanup));
+    }
+}
+
+/// Assets that have to be loaded before the game can start.
+/// Add to these in a startup system, the game stays in [`GameState::Loading`] until they're done.
+#[derive(Resource, Default, Debug)]
+pub struct RequiredAssets {
+    handles: Vec<HandleUntyped>,
+    /// Assets that failed to load, already reported
+    failed: Vec<HandleId>,
+}
+
+impl RequiredAssets {
+    pub fn add<T: Asset>(&mut self, handle: &Handle<T>) {
+        if !self.handles.iter().any(|other| other.id == handle.id()) {
+            self.handles.push(handle.clone_untyped());
+        }
+    }
+
+    /// How many of the assets have been loaded, and how many there are in total
+    pub fn progress(&self, assets: &AssetServer) -> (usize, usize) {
+        let loaded = self
+            .handles
+            .iter()
+            .filter(|handle| assets.get_load_state(handle.id) == LoadState::Loaded)
+            .count();
+        (loaded, self.handles.len())
+    }
+
+    pub fn has_failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+}
+
+/// Shows the loading progress
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct LoadingStatus;
+
+/// Tilesets and levels are loaded as dependencies of the LDtk project, and level backgrounds as
+/// dependencies of the levels, so they are only known once the project or level has loaded
+fn world_dependencies(
+    mut required: ResMut<RequiredAssets>,
+    world_query: Query<&Handle<LdtkAsset>>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_assets: Res<Assets<LdtkLevel>>,
+) {
+    for handle in world_query.iter() {
+        required.add(handle);
+        if let Some(ldtk_asset) = ldtk_assets.get(handle) {
+            for tileset in ldtk_asset.tileset_map.values() {
+                required.add(tileset);
+            }
+            for level_handle in ldtk_asset.level_map.values() {
+                required.add(level_handle);
+                if let Some(background) = level_assets
+                    .get(level_handle)
+                    .and_then(|level| level.background_image.as_ref())
+                {
+                    required.add(background);
+                }
+            }
+        }
+    }
+}
+
+fn loading_progress(
+    mut state: ResMut<State<GameState>>,
+    mut required: ResMut<RequiredAssets>,
+    settings: Res<GameStateSettings>,
+    world_query: Query<(), With<Handle<LdtkAsset>>>,
+    ldtk_enum: Res<LdtkEnum>,
+    atlases: Res<Assets<TextureAtlas>>,
+    assets: Res<AssetServer>,
+) {
+    let required = &mut *required;
+    // Assets that are retried are loading again
+    required
+        .failed
+        .retain(|id| assets.get_load_state(*id) == LoadState::Failed);
+    for handle in required.handles.iter() {
+        if assets.get_load_state(handle.id) == LoadState::Failed
+            && !required.failed.contains(&handle.id)
+        {
+            error!("Failed to load {}", asset_name(&assets, handle.id));
+            required.failed.push(handle.id);
+        }
+    }
+
+    let (loaded, total) = required.progress(&assets);
+    // The item atlas is created once the world has loaded, see `map_enum_defs`
+    if world_query.is_empty() || loaded < total || !atlases.contains(&ldtk_enum.item_atlas) {
+        return;
+    }
+
+    let next = if settings.show_title {
+        GameState::Title
+    } else {
+        GameState::Playing
+    };
+    if let Err(err) = state.set(next) {
+        warn!("Failed to finish loading: {err}");
+    }
+}
+
+/// Loads the assets that failed again on Enter, quits on Escape
+fn loading_failure_input(
+    mut required: ResMut<RequiredAssets>,
+    input: Res<Input<KeyCode>>,
+    assets: Res<AssetServer>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    if !required.has_failed() {
+        return;
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        exit_events.send(AppExit);
+    } else if input.just_pressed(KeyCode::Return) {
+        for id in std::mem::take(&mut required.failed) {
+            match assets.get_handle_path(id) {
+                Some(path) => {
+                    info!("Retrying {}", path.path().display());
+                    assets.reload_asset(path);
+                }
+                None => warn!("Can't retry {id:?}, it wasn't loaded from a file"),
+            }
+        }
+    }
+}
+
+fn asset_name(assets: &AssetServer, id: HandleId) -> String {
+    assets.get_handle_path(id).map_or_else(
+        || format!("{id:?}"),
+        |path| path.path().display().to_string(),
+    )
+}
+
+fn loading_setup(mut commands: Commands, font: Res<UiFont>) {
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section("Loading", text_style.clone()));
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(50.0), Val::Px(8.0)),
+                        margin: UiRect::all(Val::Px(16.0)),
+                        ..default()
+                    },
+                    background_color: Color::DARK_GRAY.into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                        LoadingBar,
+                    ));
+                });
+            builder.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::GRAY,
+                        ..text_style
+                    },
+                ),
+                LoadingStatus,
+            ));
+        });
+}
+
+fn loading_screen_update(
+    required: Res<RequiredAssets>,
+    assets: Res<AssetServer>,
+    mut bar_query: Query<&mut Style, With<LoadingBar>>,
+    mut status_query: Query<&mut Text, With<LoadingStatus>>,
+) {
+    let (loaded, total) = required.progress(&assets);
+    let progress = if total == 0 {
+        0.0
+    } else {
+        loaded as f32 / total as f32
+    };
+    for mut style in bar_query.iter_mut() {
+        style.size.width = Val::Percent(progress * 100.0);
+    }
+
+    for mut text in status_query.iter_mut() {
+        let section = &mut text.sections[0];
+        if required.has_failed() {
+            let failed: Vec<String> = required
+                .failed
+                .iter()
+                .map(|id| asset_name(&assets, *id))
+                .collect();
+            section.value = format!(
+                "Failed to load {}\nPress Enter to retry or Escape to quit",
+                failed.join(", ")
+            );
+            section.style.color = Color::RED;
+        } else {
+            section.value = format!("{loaded} / {total}");
+        }
+    }
+}
+
+fn loading_cleanup(mut commands: Commands, query: Query<Entity, With<LoadingScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
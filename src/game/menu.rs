@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+
+use super::{loading::RequiredAssets, state::GameState};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_ui_font);
+        for state in [GameState::Title, GameState::Paused, GameState::GameOver] {
+            app.add_system_set(SystemSet::on_enter(state).with_system(menu_setup))
+                .add_system_set(SystemSet::on_exit(state).with_system(menu_cleanup));
+        }
+    }
+}
+
+pub const UI_FONT_PATH: &str = "fonts/DejaVuSansMono.ttf";
+
+/// Font of all UI text
+#[derive(Resource, Clone, Debug)]
+pub struct UiFont(pub Handle<Font>);
+
+/// Root node of the screen shown in a menu state
+#[derive(Component)]
+pub struct MenuScreen;
+
+fn load_ui_font(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut required: ResMut<RequiredAssets>,
+) {
+    let font = assets.load(UI_FONT_PATH);
+    required.add(&font);
+    commands.insert_resource(UiFont(font));
+}
+
+fn menu_setup(mut commands: Commands, state: Res<State<GameState>>, font: Res<UiFont>) {
+    let (title, hint, background) = match state.current() {
+        GameState::Title => (
+            "SIGIL",
+            "Press Enter to start",
+            Color::rgba(0.0, 0.0, 0.0, 0.8),
+        ),
+        GameState::Paused => (
+            "Paused",
+            "Press Esc to continue",
+            Color::rgba(0.0, 0.0, 0.0, 0.5),
+        ),
+        GameState::GameOver => (
+            "Game over",
+            "Press Enter to continue",
+            Color::rgba(0.3, 0.0, 0.0, 0.6),
+        ),
+        _ => return,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: background.into(),
+                ..default()
+            },
+            MenuScreen,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 64.0,
+                    color: Color::WHITE,
+                },
+            ));
+            builder.spawn(TextBundle::from_section(
+                hint,
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 24.0,
+                    color: Color::GRAY,
+                },
+            ));
+        });
+}
+
+fn menu_cleanup(mut commands: Commands, query: Query<Entity, With<MenuScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,860 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use bevy::{input::InputSystem, prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    game::{
+        entity_instance::player::Player,
+        kinematic_actor::*,
+        ldtk::{LevelGrid, LevelGrids, SPIKES_VALUE},
+        state::is_playing,
+        tile::TILE_SIZE,
+    },
+    util::*,
+};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<NavAgent>()
+            .register_type::<NavChasePlayer>()
+            .init_resource::<NavGraphs>()
+            .add_system(nav_graph_update)
+            .add_system(nav_chase_player)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                nav_agent_input
+                    .after(InputSystem)
+                    .with_run_criteria(is_playing),
+            );
+    }
+}
+
+/// How far an actor can fall in one drop link, in tiles
+const MAX_DROP: i32 = 16;
+/// How long a path is followed before it's searched again, in seconds
+const REPATH_INTERVAL: f32 = 0.5;
+/// How close to a waypoint the actor has to be to consider it reached, in units
+const WAYPOINT_RADIUS: f32 = 2.0;
+
+/// Movement capabilities used to build a [`NavGraph`], derived from [`KaProperties`].
+///
+/// Values are rounded so that actors with nearly the same properties share a graph.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NavProfile {
+    /// Jump height in tenths of a tile
+    pub jump_height: i32,
+    /// Ground speed in units per second
+    pub speed: i32,
+}
+
+impl NavProfile {
+    pub fn from_props(props: &KaProperties) -> Self {
+        Self {
+            jump_height: (props.jump_height * 10.0).round() as i32,
+            speed: props.speed.round() as i32,
+        }
+    }
+
+    pub fn jump_height_units(&self) -> f32 {
+        self.jump_height as f32 / 10.0 * UNITS_PER_TILE
+    }
+
+    /// Maximum horizontal distance of a jump landing on the same height, in tiles
+    pub fn max_jump_distance(&self) -> i32 {
+        match velocity_required_for_jump(self.jump_height_units(), GRAVITY_COEFFICIENT) {
+            Some(jump_velocity) => {
+                let air_time = 2.0 * jump_velocity / GRAVITY_COEFFICIENT;
+                (self.speed as f32 * air_time / TILE_SIZE).floor() as i32
+            }
+            None => 0,
+        }
+    }
+
+    /// How far the cells the links of a node depend on can be from the node, in tiles.
+    ///
+    /// Returns the margins below and to the sides, and above and to the sides.
+    fn link_reach(&self) -> (Vector2I, Vector2I) {
+        let horizontal = self.max_jump_distance().max(1) + 1;
+        // Highest point of a jump, rounded up
+        let above = (self.jump_height + 9) / 10 + 1;
+        (
+            Vector2I::new(horizontal, MAX_DROP + 1),
+            Vector2I::new(horizontal, above),
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum NavLinkKind {
+    /// Walk to the neighbouring surface
+    #[default]
+    Walk,
+    /// Walk off a ledge and fall down to a surface below
+    Drop,
+    /// Jump to a surface
+    Jump,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NavLink {
+    pub target: Vector2I,
+    pub kind: NavLinkKind,
+    pub cost: f32,
+}
+
+/// A single step of a path: where to go and the input that gets there
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NavIntent {
+    pub kind: NavLinkKind,
+    /// World position of the surface cell to reach
+    pub target: Vec2,
+    pub input: KaInput,
+}
+
+/// Platformer navigation graph over all spawned levels.
+///
+/// Nodes are world space tile cells an actor can stand in: empty cells with a wall below and no
+/// spikes. Levels in a GridVania world line up with the tile grid, so links can cross from one
+/// level to its neighbours as long as both are spawned.
+#[derive(Default)]
+pub struct NavGraph {
+    pub profile: Option<NavProfile>,
+    pub nodes: HashMap<Vector2I, Vec<NavLink>>,
+}
+
+impl NavGraph {
+    pub fn build(grids: &LevelGrids, profile: NavProfile) -> Self {
+        let mut graph = Self {
+            profile: Some(profile),
+            nodes: HashMap::new(),
+        };
+        for grid in grids.iter() {
+            let (min, max) = level_cells(grid);
+            graph.build_cells(grids, profile, min, max);
+        }
+        graph
+    }
+
+    /// Rebuilds the nodes whose links can depend on the cells of the given inclusive areas, e.g.
+    /// the levels that were spawned, despawned or changed. The rest of the graph is kept.
+    pub fn update(&mut self, grids: &LevelGrids, areas: &[(Vector2I, Vector2I)]) {
+        let profile = match self.profile {
+            Some(profile) => profile,
+            None => return,
+        };
+        // A node is affected if any cell within its reach changed
+        let (below, above) = profile.link_reach();
+        let areas: Vec<(Vector2I, Vector2I)> = areas
+            .iter()
+            .map(|(min, max)| (*min - above, *max + below))
+            .collect();
+
+        self.nodes.retain(|cell, _| {
+            !areas.iter().any(|(min, max)| {
+                cell.x >= min.x && cell.y >= min.y && cell.x <= max.x && cell.y <= max.y
+            })
+        });
+        for (min, max) in areas {
+            self.build_cells(grids, profile, min, max);
+        }
+    }
+
+    /// Adds the nodes inside the inclusive area which aren't in the graph yet
+    fn build_cells(
+        &mut self,
+        grids: &LevelGrids,
+        profile: NavProfile,
+        min: Vector2I,
+        max: Vector2I,
+    ) {
+        let cells = NavCells { grids };
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = Vector2I::new(x, y);
+                if !self.nodes.contains_key(&cell) && cells.is_standable(cell) {
+                    self.nodes.insert(cell, cells.links(cell, profile));
+                }
+            }
+        }
+    }
+
+    /// Closest node at or below the position, if there's one within a few tiles
+    pub fn node_at(&self, position: Vec2) -> Option<Vector2I> {
+        let cell = world_cell(position);
+        (0..4)
+            .map(|depth| cell + Vector2I::DOWN * depth)
+            .find(|cell| self.nodes.contains_key(cell))
+    }
+
+    /// Finds the cheapest path between the surfaces below the given positions using A*.
+    ///
+    /// Returns the steps to take in order, an empty path meaning the actor is already there.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<NavIntent>> {
+        let start = self.node_at(from)?;
+        let goal = self.node_at(to)?;
+
+        let heuristic = |cell: Vector2I| {
+            let diff = goal - cell;
+            (diff.x.abs() + diff.y.abs()) as f32
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<Vector2I, (Vector2I, NavLinkKind)> = HashMap::new();
+        let mut cost_so_far: HashMap<Vector2I, f32> = HashMap::new();
+
+        open.push(OpenNode {
+            cell: start,
+            priority: heuristic(start),
+        });
+        cost_so_far.insert(start, 0.0);
+
+        while let Some(OpenNode { cell, .. }) = open.pop() {
+            if cell == goal {
+                let mut steps = Vec::new();
+                let mut current = goal;
+                while let Some((previous, kind)) = came_from.get(&current) {
+                    steps.push(intent(*previous, current, *kind));
+                    current = *previous;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+
+            let cost = cost_so_far[&cell];
+            for link in self.nodes.get(&cell).into_iter().flatten() {
+                let new_cost = cost + link.cost;
+                if cost_so_far
+                    .get(&link.target)
+                    .map_or(true, |old_cost| new_cost < *old_cost)
+                {
+                    cost_so_far.insert(link.target, new_cost);
+                    came_from.insert(link.target, (cell, link.kind));
+                    open.push(OpenNode {
+                        cell: link.target,
+                        priority: new_cost + heuristic(link.target),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Open set entry of the A* search, ordered so that [`BinaryHeap`] pops the lowest priority first
+struct OpenNode {
+    cell: Vector2I,
+    priority: f32,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+/// Tile cell containing the world position
+pub fn world_cell(position: Vec2) -> Vector2I {
+    let cell = (position / TILE_SIZE).floor();
+    Vector2I::new(cell.x as i32, cell.y as i32)
+}
+
+/// World space cells of the level, as an inclusive area
+fn level_cells(grid: &LevelGrid) -> (Vector2I, Vector2I) {
+    (
+        world_cell(grid.grid_to_world(Vector2I::ZERO)),
+        world_cell(grid.grid_to_world(grid.size - Vector2I::ONE)),
+    )
+}
+
+/// World position of the bottom center of the cell, where a standing actor's feet are
+fn cell_floor(cell: Vector2I) -> Vec2 {
+    (Vec2::from(cell) + Vec2::new(0.5, 0.0)) * TILE_SIZE
+}
+
+fn intent(from: Vector2I, to: Vector2I, kind: NavLinkKind) -> NavIntent {
+    let mut input = KaInput {
+        movement: Vec2::new((to.x - from.x).signum() as f32, 0.0),
+        ..default()
+    };
+    input.jump.set(kind == NavLinkKind::Jump);
+    NavIntent {
+        kind,
+        target: cell_floor(to),
+        input,
+    }
+}
+
+/// World space cell lookups over the level grids
+struct NavCells<'a> {
+    grids: &'a LevelGrids,
+}
+
+impl NavCells<'_> {
+    fn center(cell: Vector2I) -> Vec2 {
+        (Vec2::from(cell) + Vec2::splat(0.5)) * TILE_SIZE
+    }
+
+    /// Empty cell inside a spawned level. Spikes aren't walls, but no place to walk through either.
+    fn is_open(&self, cell: Vector2I) -> bool {
+        let center = Self::center(cell);
+        self.grids.level_at(center).map_or(false, |grid| {
+            let local = grid.world_to_grid(center);
+            !grid.is_solid(local) && grid.value(local) != Some(SPIKES_VALUE)
+        })
+    }
+
+    fn is_standable(&self, cell: Vector2I) -> bool {
+        self.is_open(cell) && self.grids.is_solid_at(Self::center(cell + Vector2I::DOWN))
+    }
+
+    fn links(&self, cell: Vector2I, profile: NavProfile) -> Vec<NavLink> {
+        let mut links = Vec::new();
+
+        for direction in [Vector2I::LEFT, Vector2I::RIGHT] {
+            let next = cell + direction;
+            if self.is_standable(next) {
+                links.push(NavLink {
+                    target: next,
+                    kind: NavLinkKind::Walk,
+                    cost: 1.0,
+                });
+            } else if self.is_open(next) {
+                // Walk off the ledge and see where we land
+                if let Some(landing) = (1..=MAX_DROP)
+                    .map(|depth| next + Vector2I::DOWN * depth)
+                    .take_while(|below| self.is_open(*below))
+                    .find(|below| self.is_standable(*below))
+                {
+                    // Never cheaper than the distance, or the A* heuristic would overestimate
+                    links.push(NavLink {
+                        target: landing,
+                        kind: NavLinkKind::Drop,
+                        cost: 1.0 + (cell.y - landing.y) as f32,
+                    });
+                }
+            }
+        }
+
+        let max_rise = profile.jump_height / 10;
+        let max_distance = profile.max_jump_distance();
+        for dy in -MAX_DROP..=max_rise {
+            for dx in -max_distance..=max_distance {
+                let target = cell + Vector2I::new(dx, dy);
+                // Straight up would go through the floor of the target, and walking is cheaper
+                if dx == 0 || (dx.abs() == 1 && dy == 0) || !self.is_standable(target) {
+                    continue;
+                }
+                if self.can_jump(cell, target, profile) {
+                    links.push(NavLink {
+                        target,
+                        kind: NavLinkKind::Jump,
+                        cost: (dx.abs() + dy.abs()) as f32 + 2.0,
+                    });
+                }
+            }
+        }
+
+        links
+    }
+
+    /// Checks that a full height jump from one surface lands on the other without hitting walls
+    fn can_jump(&self, from: Vector2I, to: Vector2I, profile: NavProfile) -> bool {
+        let jump_velocity =
+            match velocity_required_for_jump(profile.jump_height_units(), GRAVITY_COEFFICIENT) {
+                Some(jump_velocity) => jump_velocity,
+                None => return false,
+            };
+        let start = cell_floor(from);
+        let end = cell_floor(to);
+        let rise = end.y - start.y;
+
+        // Time until the jump comes back down to the height of the target
+        let discriminant = jump_velocity * jump_velocity - 2.0 * GRAVITY_COEFFICIENT * rise;
+        if discriminant < 0.0 {
+            return false;
+        }
+        let air_time = (jump_velocity + discriminant.sqrt()) / GRAVITY_COEFFICIENT;
+        let horizontal_speed = (end.x - start.x) / air_time;
+        if horizontal_speed.abs() > profile.speed as f32 {
+            return false;
+        }
+
+        // Sample the arc a few times per tile travelled
+        let samples = ((end - start).length() / TILE_SIZE * 3.0).ceil().max(4.0) as i32;
+        (1..samples).all(|i| {
+            let t = air_time * i as f32 / samples as f32;
+            let position = start
+                + Vec2::new(
+                    horizontal_speed * t,
+                    jump_velocity * t - GRAVITY_COEFFICIENT * t * t / 2.0,
+                );
+            // Sample slightly above the feet so grazing the floor doesn't count as a hit
+            self.is_open(world_cell(position + Vec2::Y))
+        })
+    }
+}
+
+/// Navigation graphs for every [`NavProfile`] used by a [`NavAgent`]
+#[derive(Resource, Default)]
+pub struct NavGraphs {
+    pub graphs: HashMap<NavProfile, NavGraph>,
+    /// Level grids the graphs were built from, by level iid
+    built_levels: HashMap<String, BuiltLevel>,
+}
+
+/// Version of a level grid a [`NavGraph`] was built from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct BuiltLevel {
+    level_entity: Entity,
+    revision: u32,
+    /// World space cells of the level, as an inclusive area
+    cells: (Vector2I, Vector2I),
+}
+
+impl BuiltLevel {
+    fn new(grid: &LevelGrid) -> Self {
+        Self {
+            level_entity: grid.level_entity,
+            revision: grid.revision(),
+            cells: level_cells(grid),
+        }
+    }
+}
+
+impl NavGraphs {
+    pub fn get(&self, props: &KaProperties) -> Option<&NavGraph> {
+        self.graphs.get(&NavProfile::from_props(props))
+    }
+}
+
+/// Follows a path towards [`NavAgent::target`] by writing to the actor's [`KaInput`]
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct NavAgent {
+    pub target: Option<Vec2>,
+    #[reflect(ignore)]
+    pub path: VecDeque<NavIntent>,
+    pub repath_timer: f32,
+}
+
+/// Makes a [`NavAgent`] chase the player
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct NavChasePlayer;
+
+/// Updates the graphs around the levels that were spawned, despawned or changed, and builds a new
+/// graph when an agent with new movement properties shows up.
+pub fn nav_graph_update(
+    agent_query: Query<&KaProperties, With<NavAgent>>,
+    level_grids: Res<LevelGrids>,
+    mut nav_graphs: ResMut<NavGraphs>,
+) {
+    let nav_graphs = &mut *nav_graphs;
+
+    let mut changed_areas = Vec::new();
+    for grid in level_grids.iter() {
+        let built = BuiltLevel::new(grid);
+        match nav_graphs.built_levels.insert(grid.iid.clone(), built) {
+            Some(old) if old == built => {}
+            Some(old) => changed_areas.extend([old.cells, built.cells]),
+            None => changed_areas.push(built.cells),
+        }
+    }
+    nav_graphs.built_levels.retain(|iid, built| {
+        let spawned = level_grids.get(iid).is_some();
+        if !spawned {
+            changed_areas.push(built.cells);
+        }
+        spawned
+    });
+
+    if !changed_areas.is_empty() {
+        for graph in nav_graphs.graphs.values_mut() {
+            graph.update(&level_grids, &changed_areas);
+        }
+        debug!(
+            "Updated navigation graphs around {count} changed level areas",
+            count = changed_areas.len()
+        );
+    }
+
+    for props in agent_query.iter() {
+        let profile = NavProfile::from_props(props);
+        if !nav_graphs.graphs.contains_key(&profile) {
+            let graph = NavGraph::build(&level_grids, profile);
+            debug!(
+                "Built navigation graph with {nodes} nodes for {profile:?}",
+                nodes = graph.nodes.len()
+            );
+            nav_graphs.graphs.insert(profile, graph);
+        }
+    }
+}
+
+fn nav_chase_player(
+    mut agent_query: Query<&mut NavAgent, With<NavChasePlayer>>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+) {
+    if let Some(player_transform) = player_query.iter().next() {
+        for mut agent in agent_query.iter_mut() {
+            agent.target = Some(player_transform.translation().truncate());
+        }
+    }
+}
+
+/// Should run in [`CoreStage::PreUpdate`] like the player input, so agents and players are
+/// handled the same way by the kinematic actor systems.
+pub fn nav_agent_input(
+    mut query: Query<(
+        &mut NavAgent,
+        &mut KaInput,
+        &KaState,
+        &KaProperties,
+        &Collider,
+        &GlobalTransform,
+    )>,
+    nav_graphs: Res<NavGraphs>,
+    time: Res<Time>,
+) {
+    for (mut agent, mut input, state, props, collider, transform) in query.iter_mut() {
+        // Pathing works with the feet of the actor
+        let half_height = match collider.as_cuboid() {
+            Some(cuboid) => cuboid.half_extents().y,
+            None => 0.0,
+        };
+        let position = transform.translation().truncate() - Vec2::Y * half_height;
+
+        agent.repath_timer -= time.delta_seconds();
+        if agent.repath_timer <= 0.0 || (agent.path.is_empty() && state.on_ground) {
+            agent.repath_timer = REPATH_INTERVAL;
+            agent.path = match (agent.target, nav_graphs.get(props)) {
+                // Only plan from the ground, otherwise the current jump or fall is interrupted
+                (Some(target), Some(graph)) if state.on_ground => {
+                    graph.find_path(position, target).unwrap_or_default().into()
+                }
+                (Some(_), Some(_)) => std::mem::take(&mut agent.path),
+                _ => VecDeque::new(),
+            };
+        }
+
+        // Drop the waypoints that have already been reached
+        while let Some(intent) = agent.path.front() {
+            let diff = intent.target - position;
+            if diff.x.abs() < WAYPOINT_RADIUS && diff.y.abs() < TILE_SIZE / 2.0 && state.on_ground {
+                agent.path.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        match agent.path.front() {
+            Some(intent) => {
+                let diff = intent.target.x - position.x;
+                input.movement = Vec2::new(
+                    if diff.abs() < WAYPOINT_RADIUS / 2.0 {
+                        0.0
+                    } else {
+                        diff.signum()
+                    },
+                    0.0,
+                );
+                // Keep holding jump for the whole jump, releasing early would short hop
+                input
+                    .jump
+                    .set(intent.input.jump.pressed() && (state.can_jump() || state.is_jumping));
+            }
+            None => {
+                input.movement = Vec2::ZERO;
+                input.jump.set(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Default jump height and speed of [`KaProperties`]
+    const PROFILE: NavProfile = NavProfile {
+        jump_height: 21,
+        speed: 50,
+    };
+
+    /// Level from rows of `#` (wall), `^` (spikes) and `.` (empty), the first row being the top one
+    fn level(iid: &str, origin: Vector2I, rows: &[&str]) -> LevelGrid {
+        let size = Vector2I::new(rows[0].len() as i32, rows.len() as i32);
+        let mut grid = LevelGrid::new(
+            iid.to_string(),
+            iid.to_string(),
+            Entity::from_raw(0),
+            Vec2::from(origin) * TILE_SIZE,
+            size,
+        );
+        for (row, line) in rows.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let cell = Vector2I::new(x as i32, size.y - 1 - row as i32);
+                match c {
+                    '#' => grid.set("GRID", cell, 1, true),
+                    '^' => grid.set("GRID", cell, SPIKES_VALUE, false),
+                    _ => {}
+                }
+            }
+        }
+        grid
+    }
+
+    fn grids(levels: impl IntoIterator<Item = LevelGrid>) -> LevelGrids {
+        let mut grids = LevelGrids::default();
+        for level in levels {
+            grids.insert(level);
+        }
+        grids
+    }
+
+    /// Feet of an actor standing in the cell
+    fn feet(x: i32, y: i32) -> Vec2 {
+        cell_floor(Vector2I::new(x, y)) + Vec2::Y
+    }
+
+    fn kinds(path: &[NavIntent]) -> Vec<NavLinkKind> {
+        path.iter().map(|intent| intent.kind).collect()
+    }
+
+    /// Cost of the path from the start cell, summing the links it follows
+    fn path_cost(graph: &NavGraph, start: Vector2I, path: &[NavIntent]) -> f32 {
+        let mut cell = start;
+        let mut cost = 0.0;
+        for intent in path {
+            let target = world_cell(intent.target + Vec2::Y);
+            cost += graph.nodes[&cell]
+                .iter()
+                .filter(|link| link.target == target)
+                .map(|link| link.cost)
+                .fold(f32::INFINITY, f32::min);
+            cell = target;
+        }
+        cost
+    }
+
+    /// Cost of the cheapest path found by Dijkstra's algorithm, which needs no heuristic
+    fn cheapest_cost(graph: &NavGraph, start: Vector2I, goal: Vector2I) -> Option<f32> {
+        let mut open = BinaryHeap::new();
+        let mut cost_so_far: HashMap<Vector2I, f32> = HashMap::new();
+        open.push(OpenNode {
+            cell: start,
+            priority: 0.0,
+        });
+        cost_so_far.insert(start, 0.0);
+        while let Some(OpenNode { cell, priority }) = open.pop() {
+            if cell == goal {
+                return Some(priority);
+            }
+            if priority > cost_so_far[&cell] {
+                continue;
+            }
+            for link in &graph.nodes[&cell] {
+                let new_cost = priority + link.cost;
+                if cost_so_far
+                    .get(&link.target)
+                    .map_or(true, |old_cost| new_cost < *old_cost)
+                {
+                    cost_so_far.insert(link.target, new_cost);
+                    open.push(OpenNode {
+                        cell: link.target,
+                        priority: new_cost,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Every link of the graph, sorted
+    fn links(graph: &NavGraph) -> Vec<(i32, i32, i32, i32, String)> {
+        let mut links: Vec<_> = graph
+            .nodes
+            .iter()
+            .flat_map(|(from, links)| {
+                links.iter().map(move |link| {
+                    (
+                        from.x,
+                        from.y,
+                        link.target.x,
+                        link.target.y,
+                        format!("{:?}", link.kind),
+                    )
+                })
+            })
+            .collect();
+        links.sort();
+        links
+    }
+
+    #[test]
+    fn walk_along_floor() {
+        let grids = grids([level("a", Vector2I::ZERO, &["......", "......", "######"])]);
+        let graph = NavGraph::build(&grids, PROFILE);
+
+        let path = graph.find_path(feet(1, 1), feet(4, 1)).unwrap();
+        assert_eq!(kinds(&path), vec![NavLinkKind::Walk; 3]);
+        assert_eq!(path.last().unwrap().target, cell_floor(Vector2I::new(4, 1)));
+        assert!(path.iter().all(|intent| intent.input.movement == Vec2::X));
+
+        assert!(graph.find_path(feet(1, 1), feet(1, 1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn jump_over_gap() {
+        let grids = grids([level(
+            "a",
+            Vector2I::ZERO,
+            &["..........", "..........", "..........", "###...####"],
+        )]);
+        let graph = NavGraph::build(&grids, PROFILE);
+
+        let path = graph.find_path(feet(1, 1), feet(8, 1)).unwrap();
+        assert!(kinds(&path).contains(&NavLinkKind::Jump));
+        assert!(!kinds(&path).contains(&NavLinkKind::Drop));
+        assert!(path.iter().any(|intent| intent.input.jump.pressed()));
+    }
+
+    #[test]
+    fn drop_down_ledge_but_not_back_up() {
+        let grids = grids([level(
+            "a",
+            Vector2I::ZERO,
+            &["........", "###.....", "........", "........", "########"],
+        )]);
+        let graph = NavGraph::build(&grids, PROFILE);
+
+        let path = graph.find_path(feet(1, 4), feet(6, 1)).unwrap();
+        assert!(kinds(&path).contains(&NavLinkKind::Drop));
+        // Three tiles up is higher than the jump
+        assert!(graph.find_path(feet(6, 1), feet(1, 4)).is_none());
+    }
+
+    #[test]
+    fn long_drop_is_the_cheapest_path() {
+        // Dropping down right away heads for the goal, but jumping to the left block and
+        // dropping from there is cheaper
+        let grids = grids([level(
+            "a",
+            Vector2I::ZERO,
+            &[
+                "............",
+                "............",
+                "............",
+                ".....#...#..",
+                "............",
+                "............",
+                "............",
+                "............",
+                "............",
+                "....#.......",
+                "############",
+            ],
+        )]);
+        let graph = NavGraph::build(&grids, PROFILE);
+
+        let start = Vector2I::new(9, 8);
+        let goal = Vector2I::new(4, 2);
+        let path = graph.find_path(feet(9, 8), feet(4, 2)).unwrap();
+        assert_eq!(kinds(&path), vec![NavLinkKind::Jump, NavLinkKind::Drop]);
+        assert_eq!(
+            Some(path_cost(&graph, start, &path)),
+            cheapest_cost(&graph, start, goal)
+        );
+    }
+
+    #[test]
+    fn links_never_cost_less_than_the_heuristic() {
+        let grids = grids([level(
+            "a",
+            Vector2I::ZERO,
+            &[
+                "..........",
+                "###.......",
+                "......##..",
+                "..........",
+                "...^^.....",
+                "##########",
+            ],
+        )]);
+        let graph = NavGraph::build(&grids, PROFILE);
+        for (from, links) in &graph.nodes {
+            for link in links {
+                let diff = link.target - *from;
+                assert!(link.cost >= (diff.x.abs() + diff.y.abs()) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn spikes_are_not_standable() {
+        let grids = grids([level(
+            "a",
+            Vector2I::ZERO,
+            &["........", "........", "........", "...^^...", "########"],
+        )]);
+        let graph = NavGraph::build(&grids, PROFILE);
+
+        assert!(!graph.nodes.contains_key(&Vector2I::new(3, 1)));
+        assert!(!graph.nodes.contains_key(&Vector2I::new(4, 1)));
+        let path = graph.find_path(feet(1, 1), feet(6, 1)).unwrap();
+        assert!(kinds(&path).contains(&NavLinkKind::Jump));
+    }
+
+    #[test]
+    fn no_path_outside_of_levels() {
+        let grids = grids([level("a", Vector2I::ZERO, &["....", "####"])]);
+        let graph = NavGraph::build(&grids, PROFILE);
+        assert!(graph.find_path(feet(1, 1), feet(20, 1)).is_none());
+    }
+
+    #[test]
+    fn update_matches_full_build() {
+        let rows: &[&str] = &[
+            "........", "........", "..##....", "........", "......##", "########",
+        ];
+        let mut grids = grids([level("a", Vector2I::ZERO, rows)]);
+        let mut graph = NavGraph::build(&grids, PROFILE);
+
+        // A neighbouring level spawns, links cross into it
+        let neighbour = level("b", Vector2I::new(8, 0), rows);
+        let area = level_cells(&neighbour);
+        grids.insert(neighbour);
+        graph.update(&grids, &[area]);
+        assert_eq!(links(&graph), links(&NavGraph::build(&grids, PROFILE)));
+        assert!(graph.find_path(feet(1, 1), feet(12, 1)).is_some());
+
+        // A wall is removed from the first level
+        let mut changed = level("a", Vector2I::ZERO, rows);
+        changed.set("GRID", Vector2I::new(6, 1), 0, false);
+        let area = level_cells(&changed);
+        grids.insert(changed);
+        graph.update(&grids, &[area]);
+        assert_eq!(links(&graph), links(&NavGraph::build(&grids, PROFILE)));
+    }
+}
@@ -0,0 +1,178 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::{prelude::*, utils::ldtk_pixel_coords_to_translation, LdtkSystemLabel};
+
+pub struct RoomPlugin;
+
+impl Plugin for RoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RoomTransitionEvent>()
+            .init_resource::<LevelBoundsIndex>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                level_bounds_index_update.after(LdtkSystemLabel::ProcessAssets),
+            );
+    }
+}
+
+/// How far outside of the current room the player has to go before switching rooms, in units.
+/// Keeps a player standing right on a boundary from toggling between the rooms every frame.
+pub const ROOM_TRANSITION_MARGIN: f32 = 4.0;
+
+/// Size of the buckets in [`LevelBoundsIndex`], in units. Matches the usual room size.
+const BUCKET_SIZE: f32 = 128.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoomDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl RoomDirection {
+    /// Which side of the room the point has left through.
+    /// If it's outside on both axes, the axis it's further out on wins.
+    pub fn from_exit(room: &Rect, point: Vec2) -> Option<Self> {
+        let outside = Vec2::new(
+            (room.min.x - point.x).max(point.x - room.max.x),
+            (room.min.y - point.y).max(point.y - room.max.y),
+        );
+        if outside.x <= 0.0 && outside.y <= 0.0 {
+            None
+        } else if outside.x >= outside.y {
+            Some(if point.x < room.min.x {
+                Self::Left
+            } else {
+                Self::Right
+            })
+        } else {
+            Some(if point.y < room.min.y {
+                Self::Down
+            } else {
+                Self::Up
+            })
+        }
+    }
+}
+
+/// Sent when the player moves from one room to another
+#[derive(Clone, Debug)]
+pub struct RoomTransitionEvent {
+    /// Level iid of the previous room, if there was one
+    pub from: Option<String>,
+    /// Level iid of the new room
+    pub to: String,
+    /// Direction the player was moving to when entering the room
+    pub direction: Option<RoomDirection>,
+}
+
+/// World space bounds of a level
+#[derive(Clone, Debug)]
+pub struct LevelBounds {
+    pub iid: String,
+    pub identifier: String,
+    pub uid: i32,
+    pub index: usize,
+    pub rect: Rect,
+}
+
+impl LevelBounds {
+    pub fn is_selected(&self, level_selection: &LevelSelection) -> bool {
+        match level_selection {
+            LevelSelection::Identifier(identifier) => *identifier == self.identifier,
+            LevelSelection::Index(index) => *index == self.index,
+            LevelSelection::Iid(iid) => *iid == self.iid,
+            LevelSelection::Uid(uid) => *uid == self.uid,
+        }
+    }
+}
+
+/// Bounds of every level in the project, bucketed on a grid for quick point lookups.
+///
+/// Built from the project instead of the spawned levels, so it also knows about the levels that
+/// haven't been streamed in yet.
+#[derive(Resource, Default)]
+pub struct LevelBoundsIndex {
+    pub levels: Vec<LevelBounds>,
+    buckets: HashMap<IVec2, Vec<usize>>,
+}
+
+impl LevelBoundsIndex {
+    pub fn from_ldtk_asset(ldtk_asset: &LdtkAsset) -> Self {
+        let world_height = ldtk_asset.world_height();
+        let levels: Vec<LevelBounds> = ldtk_asset
+            .iter_levels()
+            .enumerate()
+            .map(|(index, level)| {
+                let min = ldtk_pixel_coords_to_translation(
+                    IVec2::new(level.world_x, level.world_y + level.px_hei),
+                    world_height,
+                );
+                LevelBounds {
+                    iid: level.iid.clone(),
+                    identifier: level.identifier.clone(),
+                    uid: level.uid,
+                    index,
+                    rect: Rect::from_corners(
+                        min,
+                        min + Vec2::new(level.px_wid as f32, level.px_hei as f32),
+                    ),
+                }
+            })
+            .collect();
+
+        let mut buckets: HashMap<IVec2, Vec<usize>> = HashMap::new();
+        for (i, level) in levels.iter().enumerate() {
+            let min = Self::bucket(level.rect.min);
+            let max = Self::bucket(level.rect.max);
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    buckets.entry(IVec2::new(x, y)).or_default().push(i);
+                }
+            }
+        }
+
+        Self { levels, buckets }
+    }
+
+    fn bucket(point: Vec2) -> IVec2 {
+        (point / BUCKET_SIZE).floor().as_ivec2()
+    }
+
+    /// Level containing the point.
+    /// Points exactly on the boundary of two levels belong to either one of them.
+    pub fn level_at(&self, point: Vec2) -> Option<&LevelBounds> {
+        self.buckets
+            .get(&Self::bucket(point))?
+            .iter()
+            .map(|i| &self.levels[*i])
+            .find(|level| level.rect.contains(point))
+    }
+
+    pub fn get(&self, level_iid: &str) -> Option<&LevelBounds> {
+        self.levels.iter().find(|level| level.iid == level_iid)
+    }
+
+    pub fn selected(&self, level_selection: &LevelSelection) -> Option<&LevelBounds> {
+        self.levels
+            .iter()
+            .find(|level| level.is_selected(level_selection))
+    }
+}
+
+fn level_bounds_index_update(
+    mut ldtk_events: EventReader<AssetEvent<LdtkAsset>>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    mut index: ResMut<LevelBoundsIndex>,
+) {
+    for event in ldtk_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if let Some(ldtk_asset) = ldtk_assets.get(handle) {
+                    *index = LevelBoundsIndex::from_ldtk_asset(ldtk_asset);
+                }
+            }
+            AssetEvent::Removed { .. } => (),
+        }
+    }
+}
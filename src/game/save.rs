@@ -0,0 +1,480 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    ability::{Abilities, Ability},
+    checkpoint::RespawnPoint,
+    entity_instance::player::{move_player, Player, WorldlyQuery},
+    health::{max_health, Health},
+    item::{Inventory, ItemId},
+    kinematic_actor::KaState,
+    ldtk::{EntityInstanceAdded, WorldState},
+    room::LevelBoundsIndex,
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SavePoint>()
+            .init_resource::<SaveSettings>()
+            .init_resource::<PendingLoad>()
+            .add_event::<SaveRequest>()
+            .add_event::<LoadRequest>()
+            .add_startup_system(load_on_startup)
+            .add_system(save_point_setup)
+            .add_system(save_point_touch)
+            .add_system(save_game.after(save_point_touch))
+            .add_system(load_game)
+            .add_system(apply_pending_load.after(load_game));
+    }
+}
+
+/// Bumped whenever [`SaveData`] changes in a way older saves can't be read as is
+pub const SAVE_VERSION: u32 = 1;
+
+/// Name of the game's folder in the user's data directory
+const DATA_DIR_NAME: &str = "sigil";
+const SAVE_FILE_NAME: &str = "save.ron";
+
+#[derive(Resource)]
+pub struct SaveSettings {
+    /// Defaults to `save.ron` in the [`user_data_dir`]
+    pub path: PathBuf,
+    /// Continue from the save file when the game starts, if there is one
+    pub load_on_startup: bool,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            path: user_data_dir().unwrap_or_default().join(SAVE_FILE_NAME),
+            load_on_startup: true,
+        }
+    }
+}
+
+/// Folder of the game in the per-user data directory of the platform:
+/// - Windows: `%APPDATA%\sigil`
+/// - macOS: `~/Library/Application Support/sigil`
+/// - elsewhere: `$XDG_DATA_HOME/sigil`, or `~/.local/share/sigil`
+///
+/// `None` if the environment doesn't say where that is.
+pub fn user_data_dir() -> Option<PathBuf> {
+    let non_empty = |key: &str| env::var_os(key).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        PathBuf::from(non_empty("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(non_empty("HOME")?).join("Library/Application Support")
+    } else {
+        match non_empty("XDG_DATA_HOME") {
+            Some(data_home) => PathBuf::from(data_home),
+            None => PathBuf::from(non_empty("HOME")?).join(".local/share"),
+        }
+    };
+    Some(base.join(DATA_DIR_NAME))
+}
+
+/// Player progress as it's written to the save file.
+///
+/// Items and abilities are stored by their LDtk identifiers, so reordering the enums doesn't
+/// break old saves.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveData {
+    pub version: u32,
+    pub level_iid: String,
+    pub position: Vec2,
+    pub inventory: BTreeMap<String, u32>,
+    pub abilities: BTreeMap<String, u32>,
+    /// Iids of the collected or destroyed LDtk entities, see [`WorldState`]
+    pub removed_iids: Vec<String>,
+    /// Missing from saves made before health existed, in which case the player is at full health
+    #[serde(default)]
+    pub health: Option<u32>,
+    /// Flags set so far, see [`WorldState`]
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Serialize(err) => write!(f, "{err}"),
+            Self::Deserialize(err) => write!(f, "{err}"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "save version {version} is newer than the supported version {SAVE_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl SaveData {
+    pub fn new(
+        level_iid: String,
+        position: Vec2,
+        inventory: &Inventory,
+        abilities: &Abilities,
+        health: &Health,
+        world_state: &WorldState,
+    ) -> Self {
+        let mut removed_iids: Vec<String> = world_state.removed_iids.iter().cloned().collect();
+        removed_iids.sort();
+        let mut flags: Vec<String> = world_state.flags.iter().cloned().collect();
+        flags.sort();
+        Self {
+            version: SAVE_VERSION,
+            level_iid,
+            position,
+            inventory: inventory
+                .items
+                .iter()
+                .map(|(item, count)| (item.ldtk_id().to_string(), *count))
+                .collect(),
+            abilities: abilities
+                .unlocked
+                .iter()
+                .map(|(ability, count)| (ability.ldtk_id().to_string(), *count))
+                .collect(),
+            removed_iids,
+            health: Some(health.current),
+            flags,
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(SaveError::Serialize)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        let data: Self = ron::from_str(text).map_err(SaveError::Deserialize)?;
+        if data.version > SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(data.version));
+        }
+        Ok(data)
+    }
+
+    /// Writes the save file, creating its folder if needed
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(SaveError::Io)?;
+        }
+        fs::write(path, self.to_ron()?).map_err(SaveError::Io)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        Self::from_ron(&fs::read_to_string(path).map_err(SaveError::Io)?)
+    }
+
+    /// Inventory with unknown items skipped
+    pub fn inventory(&self) -> Inventory {
+        let mut inventory = Inventory::default();
+        for (id, count) in self.inventory.iter() {
+            match ItemId::from_ldtk(id) {
+                Some(item) => {
+                    inventory.items.insert(item, *count);
+                }
+                None => warn!("Unknown item in save: {id}"),
+            }
+        }
+        inventory
+    }
+
+    /// Abilities with unknown abilities skipped
+    pub fn abilities(&self) -> Abilities {
+        let mut abilities = Abilities::default();
+        for (id, count) in self.abilities.iter() {
+            match Ability::from_ldtk(id) {
+                Some(ability) => {
+                    abilities.unlocked.insert(ability, *count);
+                }
+                None => warn!("Unknown ability in save: {id}"),
+            }
+        }
+        abilities
+    }
+}
+
+/// Save the player's progress to [`SaveSettings::path`]
+pub struct SaveRequest;
+
+/// Load the player's progress from [`SaveSettings::path`]
+pub struct LoadRequest;
+
+/// Sensor of a `SAVE_POINT` entity
+#[derive(Reflect, Component, Default, Debug)]
+#[reflect(Component)]
+pub struct SavePoint;
+
+/// Save data waiting for the player to be spawned
+#[derive(Resource, Default)]
+struct PendingLoad(Option<SaveData>);
+
+fn load_on_startup(settings: Res<SaveSettings>, mut load_requests: EventWriter<LoadRequest>) {
+    if settings.load_on_startup && settings.path.exists() {
+        load_requests.send(LoadRequest);
+    }
+}
+
+fn save_point_setup(mut commands: Commands, mut events: EventReader<EntityInstanceAdded>) {
+    for event in events
+        .iter()
+        .filter(|e| e.instance.identifier == "SAVE_POINT")
+    {
+        commands.entity(event.entity).with_children(|builder| {
+            builder.spawn((
+                TransformBundle::default(),
+                SavePoint,
+                RigidBody::Fixed,
+                ActiveEvents::COLLISION_EVENTS,
+                ActiveCollisionTypes::KINEMATIC_STATIC,
+                Collider::cuboid(
+                    event.instance.width as f32 / 2.0,
+                    event.instance.height as f32 / 2.0,
+                ),
+                Sensor,
+            ));
+        });
+    }
+}
+
+/// Saves the game when the player touches a save point
+fn save_point_touch(
+    mut collision_events: EventReader<CollisionEvent>,
+    player_query: Query<(), With<Player>>,
+    save_point_query: Query<(), With<SavePoint>>,
+    mut save_requests: EventWriter<SaveRequest>,
+) {
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            if (player_query.contains(*e1) && save_point_query.contains(*e2))
+                || (player_query.contains(*e2) && save_point_query.contains(*e1))
+            {
+                save_requests.send(SaveRequest);
+            }
+        }
+    }
+}
+
+fn save_game(
+    mut save_requests: EventReader<SaveRequest>,
+    settings: Res<SaveSettings>,
+    player_query: Query<(&GlobalTransform, &Inventory, &Abilities, &Health), With<Player>>,
+    level_selection: Option<Res<LevelSelection>>,
+    level_bounds: Res<LevelBoundsIndex>,
+    world_state: Res<WorldState>,
+) {
+    if save_requests.iter().count() == 0 {
+        return;
+    }
+    let level_iid =
+        match level_selection.and_then(|level_selection| level_bounds.selected(&level_selection)) {
+            Some(level) => level.iid.clone(),
+            None => {
+                warn!("Can't save without a selected level");
+                return;
+            }
+        };
+
+    for (transform, inventory, abilities, health) in player_query.iter() {
+        let data = SaveData::new(
+            level_iid.clone(),
+            transform.translation().truncate(),
+            inventory,
+            abilities,
+            health,
+            &world_state,
+        );
+        match data.write(&settings.path) {
+            Ok(()) => info!("Saved to {}", settings.path.display()),
+            Err(err) => error!("Failed to save to {}: {err}", settings.path.display()),
+        }
+    }
+}
+
+/// Restores the level and the world state right away, the player is restored once it exists.
+///
+/// Spawned levels are respawned, so entities that were collected in the save disappear and the
+/// ones that weren't come back.
+fn load_game(
+    mut commands: Commands,
+    mut load_requests: EventReader<LoadRequest>,
+    settings: Res<SaveSettings>,
+    mut level_selection: Option<ResMut<LevelSelection>>,
+    mut world_state: ResMut<WorldState>,
+    mut pending: ResMut<PendingLoad>,
+    level_query: Query<Entity, With<Handle<LdtkLevel>>>,
+) {
+    if load_requests.iter().count() == 0 {
+        return;
+    }
+    let data = match SaveData::read(&settings.path) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to load {}: {err}", settings.path.display());
+            return;
+        }
+    };
+
+    if let Some(level_selection) = level_selection.as_mut() {
+        **level_selection = LevelSelection::Iid(data.level_iid.clone());
+    }
+    world_state.removed_iids = data.removed_iids.iter().cloned().collect();
+    world_state.flags = data.flags.iter().cloned().collect();
+    commands.insert_resource(RespawnPoint {
+        level_iid: data.level_iid.clone(),
+        position: data.position,
+    });
+    for level_entity in level_query.iter() {
+        commands.entity(level_entity).insert(Respawn);
+    }
+    pending.0 = Some(data);
+}
+
+/// Everything of the player a save file restores
+type LoadPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Parent,
+        &'static mut Transform,
+        &'static mut Inventory,
+        &'static mut Abilities,
+        &'static mut Health,
+        &'static mut KaState,
+    ),
+    With<Player>,
+>;
+
+/// Moves the player to the saved position and restores its progress
+fn apply_pending_load(
+    mut pending: ResMut<PendingLoad>,
+    mut player_query: LoadPlayerQuery,
+    mut worldly_query: WorldlyQuery,
+    global_query: Query<&GlobalTransform>,
+) {
+    let data = match &pending.0 {
+        Some(data) => data,
+        None => return,
+    };
+
+    let mut applied = false;
+    for (parent, mut transform, mut inventory, mut abilities, mut health, mut state) in
+        player_query.iter_mut()
+    {
+        if move_player(
+            parent,
+            &mut transform,
+            data.position,
+            &mut worldly_query,
+            &global_query,
+        ) {
+            *inventory = data.inventory();
+            *abilities = data.abilities();
+            // Set the maximum right away, so raising it doesn't fill up the restored health
+            health.reset();
+            health.max = max_health(&abilities);
+            health.current = data.health.unwrap_or(health.max).min(health.max);
+            *state = KaState::default();
+            applied = true;
+        }
+    }
+
+    if applied {
+        pending.0 = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_data() -> SaveData {
+        SaveData {
+            version: SAVE_VERSION,
+            level_iid: "a2f1c0e0-1234".to_string(),
+            position: Vec2::new(12.5, -40.0),
+            inventory: [("CROSS".to_string(), 1), ("SIGMA".to_string(), 2)].into(),
+            abilities: [("DASH".to_string(), 1)].into(),
+            removed_iids: vec!["iid-1".to_string(), "iid-2".to_string()],
+            health: Some(3),
+            flags: vec!["hermit_gate".to_string()],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = save_data();
+        let text = data.to_ron().unwrap();
+        assert_eq!(SaveData::from_ron(&text).unwrap(), data);
+    }
+
+    #[test]
+    fn round_trip_file() {
+        let dir = env::temp_dir().join(format!("sigil_save_test_{}", std::process::id()));
+        let path = dir.join("nested").join(SAVE_FILE_NAME);
+        let data = save_data();
+        data.write(&path).unwrap();
+        assert_eq!(SaveData::read(&path).unwrap(), data);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn old_save_without_health_and_flags() {
+        // Written before health and flags existed
+        let text = r#"(
+    version: 1,
+    level_iid: "a2f1c0e0-1234",
+    position: (12.5, -40.0),
+    inventory: {"CROSS": 1},
+    abilities: {},
+    removed_iids: ["iid-1"],
+)"#;
+        let data = SaveData::from_ron(text).unwrap();
+        assert_eq!(data.health, None);
+        assert!(data.flags.is_empty());
+        assert_eq!(data.level_iid, "a2f1c0e0-1234");
+        assert_eq!(data.position, Vec2::new(12.5, -40.0));
+        assert_eq!(data.inventory.get("CROSS"), Some(&1));
+        assert_eq!(data.removed_iids, vec!["iid-1".to_string()]);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut data = save_data();
+        data.version = SAVE_VERSION + 1;
+        let text = data.to_ron().unwrap();
+        assert!(matches!(
+            SaveData::from_ron(&text),
+            Err(SaveError::UnsupportedVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn unknown_items_are_skipped() {
+        let mut data = save_data();
+        data.inventory.insert("NOT_AN_ITEM".to_string(), 1);
+        data.abilities.insert("NOT_AN_ABILITY".to_string(), 1);
+        assert_eq!(data.inventory().items.len(), data.inventory.len() - 1);
+        assert_eq!(data.abilities().unlocked.len(), data.abilities.len() - 1);
+    }
+}
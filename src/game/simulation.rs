@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_ecs_ldtk::prelude::*;
+
+use super::{
+    entity_instance::player::{Player, PlayerInputSystem},
+    kinematic_actor::KaInput,
+    room::RoomTransitionEvent,
+    save::SaveSettings,
+    state::GameState,
+    SigilConfig, SigilPlugin,
+};
+
+/// Length of a simulated frame
+pub const SIMULATION_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Headless game stepped one fixed-length frame at a time, for testing gameplay.
+///
+/// The players are controlled with [`ScriptedInput`] instead of the keyboard.
+pub struct Simulation {
+    pub app: App,
+    /// Frames simulated so far
+    pub frame: u64,
+    time: Instant,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::with_config(SigilConfig::headless())
+    }
+
+    /// Simulation of a customized game. The window setting is ignored, simulations are always headless.
+    pub fn with_config(config: SigilConfig) -> Self {
+        let mut app = App::new();
+        app.add_plugin(SigilPlugin::new(SigilConfig {
+            window: None,
+            show_title: false,
+            ..config
+        }))
+        .add_plugin(SimulationPlugin);
+        Self {
+            app,
+            frame: 0,
+            time: Instant::now(),
+        }
+    }
+
+    /// Simulates one frame
+    pub fn step(&mut self) {
+        self.time += SIMULATION_STEP;
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.time));
+        self.app.update();
+        self.frame += 1;
+    }
+
+    pub fn run(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Steps until the condition holds, for at most the given number of frames.
+    /// Returns whether the condition was met.
+    pub fn run_until(
+        &mut self,
+        frames: u32,
+        mut condition: impl FnMut(&mut World) -> bool,
+    ) -> bool {
+        for _ in 0..frames {
+            self.step();
+            if condition(&mut self.app.world) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Steps until the world and the player have been spawned and the game is running.
+    /// Assets load in the background, so this is limited by real time instead of frames.
+    pub fn load(&mut self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            self.step();
+            if self.state() == GameState::Playing && self.player_position().is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn set_input(&mut self, input: ScriptedInput) {
+        self.app.insert_resource(input);
+    }
+
+    pub fn player(&mut self) -> Option<Entity> {
+        self.app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(&self.app.world)
+            .next()
+    }
+
+    pub fn player_position(&mut self) -> Option<Vec2> {
+        self.app
+            .world
+            .query_filtered::<&GlobalTransform, With<Player>>()
+            .iter(&self.app.world)
+            .next()
+            .map(|transform| transform.translation().truncate())
+    }
+
+    pub fn state(&self) -> GameState {
+        *self.app.world.resource::<State<GameState>>().current()
+    }
+
+    /// Switches the state on the next step, e.g. to pause the game
+    pub fn set_state(&mut self, state: GameState) {
+        self.app
+            .world
+            .resource_mut::<State<GameState>>()
+            .set(state)
+            .ok();
+    }
+
+    pub fn level_selection(&self) -> Option<&LevelSelection> {
+        self.app.world.get_resource::<LevelSelection>()
+    }
+
+    /// Every room transition since the simulation started
+    pub fn room_transitions(&self) -> &[RoomTransitionEvent] {
+        &self.app.world.resource::<SimulationLog>().room_transitions
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Input given to the players of a [`Simulation`] instead of the keyboard
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct ScriptedInput {
+    pub movement: Vec2,
+    pub jump: bool,
+    pub dash: bool,
+    pub interact: bool,
+}
+
+/// Events recorded during a [`Simulation`]
+#[derive(Resource, Default, Debug)]
+pub struct SimulationLog {
+    pub room_transitions: Vec<RoomTransitionEvent>,
+}
+
+struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScriptedInput>()
+            .init_resource::<SimulationLog>()
+            // Keep away from the save file of whoever is running the simulation, and from the
+            // working directory
+            .insert_resource(SaveSettings {
+                path: std::env::temp_dir().join("sigil_simulation_save.ron"),
+                load_on_startup: false,
+            })
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                scripted_input.after(PlayerInputSystem),
+            )
+            .add_system_to_stage(CoreStage::Last, record_room_transitions);
+    }
+}
+
+/// Replaces the keyboard input of the players.
+/// Only the current button states are set, the previous ones were already moved by the keyboard
+/// input, so that just pressed and released still work.
+fn scripted_input(input: Res<ScriptedInput>, mut query: Query<&mut KaInput, With<Player>>) {
+    for mut ka_input in query.iter_mut() {
+        ka_input.movement = input.movement;
+        ka_input.jump.current = input.jump;
+        ka_input.dash.current = input.dash;
+        ka_input.interact.current = input.interact;
+    }
+}
+
+fn record_room_transitions(
+    mut events: EventReader<RoomTransitionEvent>,
+    mut log: ResMut<SimulationLog>,
+) {
+    log.room_transitions.extend(events.iter().cloned());
+}
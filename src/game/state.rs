@@ -0,0 +1,89 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use super::{entity_instance::player::Player, health::DeathEvent};
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(GameState::Loading)
+            .init_resource::<GameStateSettings>()
+            .add_system(state_input)
+            .add_system(game_over_on_death)
+            .add_system(physics_pause);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    /// Waiting for the [`super::loading::RequiredAssets`]
+    Loading,
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Resource)]
+pub struct GameStateSettings {
+    /// Show the title screen after loading, instead of going straight into the game
+    pub show_title: bool,
+}
+
+impl Default for GameStateSettings {
+    fn default() -> Self {
+        Self { show_title: true }
+    }
+}
+
+/// Run criteria for systems that should be frozen outside of [`GameState::Playing`].
+///
+/// States only drive [`CoreStage::Update`], this works in any stage.
+pub fn is_playing(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn state_input(mut state: ResMut<State<GameState>>, input: Res<Input<KeyCode>>) {
+    let next = match state.current() {
+        GameState::Title | GameState::GameOver if input.just_pressed(KeyCode::Return) => {
+            GameState::Playing
+        }
+        GameState::Playing if input.just_pressed(KeyCode::Escape) => GameState::Paused,
+        GameState::Paused
+            if input.just_pressed(KeyCode::Escape) || input.just_pressed(KeyCode::Return) =>
+        {
+            GameState::Playing
+        }
+        _ => return,
+    };
+    // Fails if a transition is already queued this frame, which can be ignored
+    state.set(next).ok();
+}
+
+/// The dead player waits on the game over screen, and comes back at the
+/// [`super::checkpoint::RespawnPoint`] when it's left
+fn game_over_on_death(
+    mut state: ResMut<State<GameState>>,
+    mut death_events: EventReader<DeathEvent>,
+    player_query: Query<(), With<Player>>,
+) {
+    if death_events
+        .iter()
+        .any(|event| player_query.contains(event.entity))
+        && *state.current() == GameState::Playing
+    {
+        state.set(GameState::GameOver).ok();
+    }
+}
+
+fn physics_pause(state: Res<State<GameState>>, mut rapier_config: ResMut<RapierConfiguration>) {
+    let playing = *state.current() == GameState::Playing;
+    if rapier_config.physics_pipeline_active != playing {
+        rapier_config.physics_pipeline_active = playing;
+    }
+}
@@ -0,0 +1,4 @@
+pub mod game;
+pub mod util;
+
+pub use game::{SigilConfig, SigilPlugin};
@@ -1,6 +1,3 @@
-pub mod game;
-pub mod util;
-
 fn main() {
-    game::init();
+    sigil::game::init();
 }
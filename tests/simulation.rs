@@ -0,0 +1,360 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use sigil::game::{
+    entity_instance::enemy::Enemy,
+    health::{DeathEvent, Health},
+    kinematic_actor::KaState,
+    ldtk::{WallCollider, WallColliderMode, WallColliderSettings},
+    level_streaming::LevelStreaming,
+    loading::RequiredAssets,
+    navigation::NavAgent,
+    room::{LevelBoundsIndex, RoomDirection},
+    simulation::{ScriptedInput, Simulation},
+    state::GameState,
+    SigilConfig, SigilSubsystems,
+};
+
+/// Assets are loaded from disk in the background, give them plenty of time on slow machines
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn loaded_simulation() -> Simulation {
+    load(Simulation::new())
+}
+
+fn load(mut simulation: Simulation) -> Simulation {
+    assert!(
+        simulation.load(LOAD_TIMEOUT),
+        "The world and the player weren't spawned in time"
+    );
+    simulation
+}
+
+fn level_rect(simulation: &Simulation, identifier: &str) -> Rect {
+    simulation
+        .app
+        .world
+        .resource::<LevelBoundsIndex>()
+        .levels
+        .iter()
+        .find(|level| level.identifier == identifier)
+        .map(|level| level.rect)
+        .unwrap_or_else(|| panic!("No level {identifier}"))
+}
+
+fn level_iid(simulation: &Simulation, identifier: &str) -> String {
+    simulation
+        .app
+        .world
+        .resource::<LevelBoundsIndex>()
+        .levels
+        .iter()
+        .find(|level| level.identifier == identifier)
+        .map(|level| level.iid.clone())
+        .unwrap_or_else(|| panic!("No level {identifier}"))
+}
+
+/// Holds a direction and keeps jumping, pressing the button for `held` frames out of every
+/// `period`. Releasing it early cuts the jump short.
+fn run_jumping(
+    simulation: &mut Simulation,
+    movement: Vec2,
+    (period, held): (u32, u32),
+    frames: u32,
+) {
+    for frame in 0..frames {
+        simulation.set_input(ScriptedInput {
+            movement,
+            jump: frame % period < held,
+            ..default()
+        });
+        simulation.step();
+    }
+}
+
+#[test]
+fn player_spawns_in_start_room() {
+    let mut simulation = loaded_simulation();
+    let position = simulation.player_position().unwrap();
+    assert!(level_rect(&simulation, "ROOM_0").contains(position));
+    assert!(simulation.room_transitions().is_empty());
+}
+
+#[test]
+fn player_stands_on_the_floor() {
+    let mut simulation = loaded_simulation();
+    simulation.run(60);
+    let start = simulation.player_position().unwrap();
+    simulation.run(120);
+    let position = simulation.player_position().unwrap();
+    assert!(
+        (position - start).length() < 0.5,
+        "The player moved from {start} to {position} without input"
+    );
+}
+
+#[test]
+fn walk_right_into_next_room() {
+    let mut simulation = loaded_simulation();
+    let start = simulation.player_position().unwrap();
+    let room_1 = level_rect(&simulation, "ROOM_1");
+
+    run_jumping(&mut simulation, Vec2::X, (16, 12), 600);
+
+    let position = simulation.player_position().unwrap();
+    assert!(
+        room_1.contains(position),
+        "The player walked from {start} to {position}, outside of ROOM_1 {room_1:?}"
+    );
+    let transitions = simulation.room_transitions();
+    assert_eq!(transitions.len(), 1, "{transitions:?}");
+    assert_eq!(
+        transitions[0].from.as_deref(),
+        Some(level_iid(&simulation, "ROOM_0").as_str())
+    );
+    assert_eq!(transitions[0].to, level_iid(&simulation, "ROOM_1"));
+    assert_eq!(transitions[0].direction, Some(RoomDirection::Right));
+}
+
+#[test]
+fn walk_left_and_back() {
+    let mut simulation = loaded_simulation();
+    let room_0 = level_rect(&simulation, "ROOM_0");
+    let room_4 = level_rect(&simulation, "ROOM_4");
+
+    run_jumping(&mut simulation, Vec2::NEG_X, (40, 20), 600);
+    let position = simulation.player_position().unwrap();
+    assert!(room_4.contains(position), "{position} is outside of ROOM_4");
+
+    run_jumping(&mut simulation, Vec2::X, (40, 20), 300);
+    let position = simulation.player_position().unwrap();
+    assert!(room_0.contains(position), "{position} is outside of ROOM_0");
+
+    let to: Vec<&str> = simulation
+        .room_transitions()
+        .iter()
+        .map(|transition| transition.to.as_str())
+        .collect();
+    assert_eq!(
+        to,
+        vec![
+            level_iid(&simulation, "ROOM_4").as_str(),
+            level_iid(&simulation, "ROOM_0").as_str()
+        ]
+    );
+}
+
+#[test]
+fn neighbours_are_spawned_without_level_streaming() {
+    let mut simulation = load(Simulation::with_config(SigilConfig {
+        subsystems: SigilSubsystems {
+            level_streaming: false,
+            ..default()
+        },
+        ..SigilConfig::headless()
+    }));
+    let level_set = simulation
+        .app
+        .world
+        .query::<&LevelSet>()
+        .single(&simulation.app.world)
+        .clone();
+    for identifier in ["ROOM_0", "ROOM_1", "ROOM_4"] {
+        assert!(
+            level_set.iids.contains(&level_iid(&simulation, identifier)),
+            "{identifier} isn't spawned"
+        );
+    }
+
+    run_jumping(&mut simulation, Vec2::X, (16, 12), 600);
+    let position = simulation.player_position().unwrap();
+    assert!(level_rect(&simulation, "ROOM_1").contains(position));
+    assert_eq!(simulation.room_transitions().len(), 1);
+}
+
+#[test]
+fn walk_on_outline_colliders() {
+    let mut simulation = load(Simulation::with_config(SigilConfig {
+        wall_colliders: WallColliderSettings {
+            default_mode: WallColliderMode::Outline,
+            ..default()
+        },
+        ..SigilConfig::headless()
+    }));
+    let mut colliders = simulation
+        .app
+        .world
+        .query_filtered::<&Collider, With<WallCollider>>();
+    let colliders: Vec<&Collider> = colliders.iter(&simulation.app.world).collect();
+    assert!(!colliders.is_empty());
+    assert!(colliders
+        .iter()
+        .all(|collider| collider.as_polyline().is_some()));
+
+    simulation.run(60);
+    let start = simulation.player_position().unwrap();
+    simulation.run(120);
+    assert!((simulation.player_position().unwrap() - start).length() < 0.5);
+
+    run_jumping(&mut simulation, Vec2::X, (16, 12), 600);
+    let position = simulation.player_position().unwrap();
+    assert!(
+        level_rect(&simulation, "ROOM_1").contains(position),
+        "{position} is outside of ROOM_1"
+    );
+}
+
+#[test]
+fn enemies_chase_the_player() {
+    let mut simulation = Simulation::new();
+    // The only enemy is three rooms away from the start
+    simulation
+        .app
+        .world
+        .resource_mut::<LevelStreaming>()
+        .neighbour_radius = 3;
+    let mut simulation = load(simulation);
+    simulation.run(120);
+
+    let player = simulation.player_position().unwrap();
+    let mut enemies = simulation
+        .app
+        .world
+        .query_filtered::<(&NavAgent, &KaState, &GlobalTransform), With<Enemy>>();
+    let enemies: Vec<_> = enemies.iter(&simulation.app.world).collect();
+    assert_eq!(enemies.len(), 1);
+    let (agent, state, transform) = enemies[0];
+    assert_eq!(agent.target, Some(player));
+    assert!(state.on_ground);
+    let position = transform.translation().truncate();
+    assert!(
+        simulation
+            .app
+            .world
+            .resource::<LevelBoundsIndex>()
+            .level_at(position)
+            .is_some(),
+        "The enemy fell out of the world to {position}"
+    );
+}
+
+fn spawned_levels(simulation: &mut Simulation) -> Vec<String> {
+    let level_set = simulation
+        .app
+        .world
+        .query::<&LevelSet>()
+        .single(&simulation.app.world);
+    level_set.iids.iter().cloned().collect()
+}
+
+#[test]
+fn pausing_freezes_the_game() {
+    let mut simulation = loaded_simulation();
+    let room_4 = level_iid(&simulation, "ROOM_4");
+
+    // Into ROOM_1, which leaves ROOM_4 unneeded
+    for frame in 0..600 {
+        simulation.set_input(ScriptedInput {
+            movement: Vec2::X,
+            jump: frame % 16 < 12,
+            ..default()
+        });
+        simulation.step();
+        if !simulation.room_transitions().is_empty() {
+            break;
+        }
+    }
+    assert_eq!(simulation.room_transitions().len(), 1);
+
+    simulation.set_state(GameState::Paused);
+    simulation.step();
+    assert_eq!(simulation.state(), GameState::Paused);
+    let position = simulation.player_position().unwrap();
+    simulation.run(600);
+    assert_eq!(simulation.player_position().unwrap(), position);
+    assert!(
+        spawned_levels(&mut simulation).contains(&room_4),
+        "ROOM_4 was unloaded while paused"
+    );
+
+    simulation.set_state(GameState::Playing);
+    simulation.set_input(ScriptedInput::default());
+    simulation.run(300);
+    assert!(!spawned_levels(&mut simulation).contains(&room_4));
+}
+
+#[test]
+fn respawn_after_game_over() {
+    let mut simulation = loaded_simulation();
+    simulation.run(60);
+    let start = simulation.player_position().unwrap();
+    run_jumping(&mut simulation, Vec2::X, (16, 12), 60);
+    simulation.set_input(ScriptedInput::default());
+
+    let player = simulation.player().unwrap();
+    simulation
+        .app
+        .world
+        .get_mut::<Health>(player)
+        .unwrap()
+        .current = 0;
+    simulation
+        .app
+        .world
+        .send_event(DeathEvent { entity: player });
+    simulation.run(2);
+    assert_eq!(simulation.state(), GameState::GameOver);
+
+    // Nothing happens until the game over screen is left
+    let position = simulation.player_position().unwrap();
+    simulation.run(60);
+    assert_eq!(simulation.player_position().unwrap(), position);
+    assert!(simulation
+        .app
+        .world
+        .get::<Health>(player)
+        .unwrap()
+        .is_dead());
+
+    simulation.set_state(GameState::Playing);
+    simulation.step();
+    assert_eq!(simulation.state(), GameState::Playing);
+    assert!(!simulation
+        .app
+        .world
+        .get::<Health>(player)
+        .unwrap()
+        .is_dead());
+    simulation.run(60);
+    let position = simulation.player_position().unwrap();
+    assert!(
+        (position - start).length() < 8.0,
+        "The player respawned at {position} instead of {start}"
+    );
+}
+
+#[test]
+fn missing_world_fails_to_load() {
+    let mut simulation = Simulation::with_config(SigilConfig {
+        world_path: "levels/missing.ldtk".to_string(),
+        ..SigilConfig::headless()
+    });
+    // Like loading, failing happens in the background
+    let start = Instant::now();
+    while !simulation
+        .app
+        .world
+        .resource::<RequiredAssets>()
+        .has_failed()
+    {
+        assert!(
+            start.elapsed() < LOAD_TIMEOUT,
+            "The missing world wasn't reported"
+        );
+        simulation.step();
+    }
+    simulation.run(10);
+    assert_eq!(simulation.state(), GameState::Loading);
+}